@@ -0,0 +1,255 @@
+use crate::bit_matrix::BitMatrix;
+use crate::character_set::decode_bytes;
+use crate::reed_solomon::{correct_errors, GaloisField};
+
+/// Largest number of the data layers in the compact and full-range symbols.
+const COMPACT_MAX_LAYERS: usize = 4;
+const FULL_MAX_LAYERS: usize = 32;
+
+/// Mode message is protected with GF(16), the data codewords with a field that depends on the number of the layers.
+const MODE_MESSAGE_FIELD: (usize, usize) = (0x13, 16);
+
+/// Group separator that replaces FNC1 in the GS1 data.
+const GS: u8 = 0x1d;
+
+/**
+Character tables of the latch modes, None is a latch, shift or flag code.
+Two character codes like ". " are returned as they are.
+**/
+const UPPER_TABLE: [Option<&str>; 28] = [None, Some(" "), Some("A"), Some("B"), Some("C"), Some("D"), Some("E"), Some("F"), Some("G"),
+    Some("H"), Some("I"), Some("J"), Some("K"), Some("L"), Some("M"), Some("N"), Some("O"), Some("P"), Some("Q"), Some("R"),
+    Some("S"), Some("T"), Some("U"), Some("V"), Some("W"), Some("X"), Some("Y"), Some("Z")];
+const LOWER_TABLE: [Option<&str>; 28] = [None, Some(" "), Some("a"), Some("b"), Some("c"), Some("d"), Some("e"), Some("f"), Some("g"),
+    Some("h"), Some("i"), Some("j"), Some("k"), Some("l"), Some("m"), Some("n"), Some("o"), Some("p"), Some("q"), Some("r"),
+    Some("s"), Some("t"), Some("u"), Some("v"), Some("w"), Some("x"), Some("y"), Some("z")];
+const MIXED_TABLE: [Option<&str>; 28] = [None, Some(" "), Some("\x01"), Some("\x02"), Some("\x03"), Some("\x04"), Some("\x05"),
+    Some("\x06"), Some("\x07"), Some("\x08"), Some("\t"), Some("\n"), Some("\x0b"), Some("\x0c"), Some("\r"), Some("\x1b"), Some("\x1c"),
+    Some("\x1d"), Some("\x1e"), Some("\x1f"), Some("@"), Some("\\"), Some("^"), Some("_"), Some("`"), Some("|"), Some("~"), Some("\x7f")];
+const PUNCT_TABLE: [Option<&str>; 31] = [None, Some("\r"), Some("\r\n"), Some(". "), Some(", "), Some(": "), Some("!"), Some("\""),
+    Some("#"), Some("$"), Some("%"), Some("&"), Some("'"), Some("("), Some(")"), Some("*"), Some("+"), Some(","), Some("-"), Some("."),
+    Some("/"), Some(":"), Some(";"), Some("<"), Some("="), Some(">"), Some("?"), Some("["), Some("]"), Some("{"), Some("}")];
+const DIGIT_TABLE: [Option<&str>; 14] = [None, Some(" "), Some("0"), Some("1"), Some("2"), Some("3"), Some("4"), Some("5"), Some("6"),
+    Some("7"), Some("8"), Some("9"), Some(","), Some(".")];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Upper,
+    Lower,
+    Mixed,
+    Punct,
+    Digit,
+    Binary,
+}
+
+///Side of the symbol in modules, full-range symbols have the reference grid lines after every 15 modules.
+pub fn symbol_size(compact: bool, layers: usize) -> usize {
+    if compact {
+        11 + 4 * layers
+    } else {
+        let base = 14 + 4 * layers;
+        base + 1 + 2 * ((base / 2 - 1) / 15)
+    }
+}
+
+/**
+Reads the number of the layers and the data codewords from the mode message around the bullseye.
+Parameters:
+bits - 28 bits of the compact or 40 bits of the full-range mode message, clockwise from the top left corner.
+compact - symbol is compact.
+**/
+pub fn read_mode_message(bits: &[bool], compact: bool) -> Option<(usize, usize)> {
+    let mut words: Vec<u16> = bits.chunks(4).map(|w| w.iter().fold(0, |acc, b| acc << 1 | *b as u16)).collect();
+    let data_words = if compact {2} else {4};
+    let field = GaloisField::new(MODE_MESSAGE_FIELD.0, MODE_MESSAGE_FIELD.1, 1);
+    let ec_count = words.len() - data_words;
+    correct_errors(&field, &mut words, ec_count)?;
+    let value = words[..data_words].iter().fold(0, |acc, w| acc << 4 | *w as usize);
+    let (layers, codewords) = if compact {(value >> 6, value & 0x3f)} else {(value >> 11, value & 0x7ff)};
+    if layers + 1 > if compact {COMPACT_MAX_LAYERS} else {FULL_MAX_LAYERS} {
+        return None;
+    }
+    Some((layers + 1, codewords + 1))
+}
+
+/**
+Decodes the sampled Aztec modules to text.
+Returns None if there are too many errors or the data is not valid.
+Parameters:
+grid - one bit per module in the upright orientation, the bullseye in the middle.
+compact - symbol is compact.
+layers, data_codewords - values from the mode message.
+**/
+pub fn decode_aztec(grid: &BitMatrix, compact: bool, layers: usize, data_codewords: usize) -> Option<String> {
+    let raw = layer_bits(grid, compact, layers);
+    let (word_size, primitive) = match layers {
+        1..=2 => (6, 0x43),
+        3..=8 => (8, 0x12d),
+        9..=22 => (10, 0x409),
+        _ => (12, 0x1069),
+    };
+    // Codewords are aligned to the end of the layers, the first bits are not used.
+    let offset = raw.len() % word_size;
+    let mut words: Vec<u16> = raw[offset..].chunks(word_size)
+        .map(|w| w.iter().fold(0, |acc, b| acc << 1 | *b as u16))
+        .collect();
+    if data_codewords >= words.len() {
+        return None;
+    }
+    let field = GaloisField::new(primitive, 1 << word_size, 1);
+    let ec_count = words.len() - data_codewords;
+    correct_errors(&field, &mut words, ec_count)?;
+    // Codewords of all zeros or ones are not allowed, the ones with one differing last bit are stuffed.
+    let mask = (1 << word_size) - 1;
+    let mut bits = Vec::with_capacity(data_codewords * word_size);
+    for word in words[..data_codewords].iter() {
+        match *word {
+            w if w == 0 || w == mask => return None,
+            w if w == 1 || w == mask - 1 => bits.extend(std::iter::repeat_n(w > 1, word_size - 1)),
+            w => bits.extend((0..word_size).rev().map(|bit| w >> bit & 1 == 1)),
+        }
+    }
+    decode_bits(&bits)
+}
+
+/**
+Reads the data layers from the outermost to the bullseye. Every layer is two modules wide
+and is read in the dominoes of two bits clockwise, starting from the top left corner.
+**/
+fn layer_bits(grid: &BitMatrix, compact: bool, layers: usize) -> Vec<bool> {
+    let base = if compact {11} else {14} + 4 * layers;
+    // Module positions without the reference grid lines.
+    let alignment: Vec<usize> = if compact {
+        (0..base).collect()
+    } else {
+        let center = grid.width / 2;
+        let mut alignment = vec![0; base];
+        for i in 0..base / 2 {
+            let offset = i + i / 15;
+            alignment[base / 2 - i - 1] = center - offset - 1;
+            alignment[base / 2 + i] = center + offset + 1;
+        }
+        alignment
+    };
+    let mut bits = Vec::new();
+    for layer in 0..layers {
+        let row_size = (layers - layer) * 4 + if compact {9} else {12};
+        let low = layer * 2;
+        let high = base - 1 - low;
+        let mut sides = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+        for j in 0..row_size {
+            for k in 0..2 {
+                sides[0].push(grid.get(alignment[low + k], alignment[low + j]));
+                sides[1].push(grid.get(alignment[low + j], alignment[high - k]));
+                sides[2].push(grid.get(alignment[high - k], alignment[high - j]));
+                sides[3].push(grid.get(alignment[high - j], alignment[low + k]));
+            }
+        }
+        for side in sides.iter() {
+            bits.extend_from_slice(side);
+        }
+    }
+    bits
+}
+
+fn read(bits: &[bool], pos: &mut usize, count: usize) -> Option<u32> {
+    let value = bits.get(*pos..*pos + count)?.iter().fold(0, |acc, b| acc << 1 | *b as u32);
+    *pos += count;
+    Some(value)
+}
+
+/**
+Character modes are latched or shifted for one character, the binary shift returns to the mode it was shifted from.
+FLG(0) is FNC1, in the first position it marks GS1 data. FLG(1) - FLG(6) are ECI numbers of 1 - 6 digits.
+**/
+fn decode_bits(bits: &[bool]) -> Option<String> {
+    let mut text = String::new();
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut eci = None;
+    let mut latch = Mode::Upper;
+    let mut shift = Mode::Upper;
+    let mut pos = 0;
+    while pos < bits.len() {
+        if shift == Mode::Binary {
+            // Last codeword is padded with ones, so the data can end with an incomplete binary shift.
+            let length = match read(bits, &mut pos, 5) {
+                Some(0) => read(bits, &mut pos, 11).map(|l| l as usize + 31),
+                length => length.map(|l| l as usize),
+            };
+            for _ in 0..length.unwrap_or(0) {
+                match read(bits, &mut pos, 8) {
+                    Some(byte) => bytes.push(byte as u8),
+                    None => break,
+                }
+            }
+            if length.is_none() {
+                break;
+            }
+            shift = latch;
+            continue;
+        }
+        let code = match read(bits, &mut pos, if shift == Mode::Digit {4} else {5}) {
+            Some(code) => code as usize,
+            None => break,
+        };
+        let table: &[Option<&str>] = match shift {
+            Mode::Upper => &UPPER_TABLE,
+            Mode::Lower => &LOWER_TABLE,
+            Mode::Mixed => &MIXED_TABLE,
+            Mode::Punct => &PUNCT_TABLE,
+            _ => &DIGIT_TABLE,
+        };
+        if let Some(Some(chars)) = table.get(code) {
+            bytes.extend(chars.bytes());
+            shift = latch;
+            continue;
+        }
+        let (next, is_latch) = match (shift, code) {
+            (Mode::Punct, 0) => {
+                let n = match read(bits, &mut pos, 3) {
+                    Some(n) => n,
+                    None => break,
+                };
+                match n {
+                    0 if text.is_empty() && bytes.is_empty() => {}
+                    0 => bytes.push(GS),
+                    1..=6 => {
+                        let mut value = 0;
+                        for _ in 0..n {
+                            let digit = read(bits, &mut pos, 4)?;
+                            if !(2..=11).contains(&digit) {
+                                return None;
+                            }
+                            value = value * 10 + digit - 2;
+                        }
+                        text.push_str(&decode_bytes(&bytes, eci)?);
+                        bytes.clear();
+                        eci = Some(value);
+                    }
+                    _ => return None,
+                }
+                shift = latch;
+                continue;
+            }
+            (Mode::Punct, _) => (Mode::Upper, true),
+            (_, 0) => (Mode::Punct, false),
+            (Mode::Digit, 14) => (Mode::Upper, true),
+            (Mode::Digit, _) => (Mode::Upper, false),
+            (Mode::Upper, 28) => (Mode::Lower, true),
+            (Mode::Lower, 28) => (Mode::Upper, false),
+            (Mode::Mixed, 28) => (Mode::Lower, true),
+            (Mode::Mixed, 29) => (Mode::Upper, true),
+            (_, 29) => (Mode::Mixed, true),
+            (Mode::Mixed, 30) => (Mode::Punct, true),
+            (_, 30) => (Mode::Digit, true),
+            _ => (Mode::Binary, false),
+        };
+        // Shift returns to the mode that was active when it was invoked, even if that was a shift.
+        latch = shift;
+        shift = next;
+        if is_latch {
+            latch = next;
+        }
+    }
+    text.push_str(&decode_bytes(&bytes, eci)?);
+    Some(text)
+}
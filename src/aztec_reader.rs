@@ -0,0 +1,470 @@
+use crate::aztec_decoder::{decode_aztec, read_mode_message, symbol_size};
+use crate::bit_matrix::BitMatrix;
+use crate::dark_area::{fill_dark_area, hull_quadrilateral, is_dark, lerp, Point};
+use crate::grid_sampler::{sample_grid, PerspectiveTransform};
+use crate::symbology::{Barcode, Symbology};
+
+/// Number of the equal runs on the line through the center of the compact bullseye.
+const BULLSEYE_RUNS: usize = 9;
+
+/// Ring of the bullseye that is used for the first estimate of the symbol orientation.
+const FIRST_RING: usize = 2;
+
+/// Edge of a bullseye ring has to be found from this many points to fit a line to it.
+const MIN_EDGE_POINTS: usize = 4;
+
+/// Orientation marks have to match with at least this many of their 12 modules.
+const MIN_ORIENTATION_MATCHES: usize = 10;
+
+/// Corrections of the rotation in degrees and of the scale of the fitted bullseye, in the order they are tried.
+const ROTATION_CORRECTIONS: [f32; 9] = [0.0, -0.75, 0.75, -1.5, 1.5, -2.25, 2.25, -3.0, 3.0];
+const SCALE_CORRECTIONS: [f32; 5] = [1.0, 0.99, 1.01, 0.98, 1.02];
+
+/**
+Orientation marks in the corners of the mode message ring as the corner signs,
+the offset from the corner and the expected color. There are 3 dark modules in the
+top left corner, 2 in the top right, 1 in the bottom right and none in the bottom left.
+**/
+const ORIENTATION_MARKS: [(i32, i32, i32, i32, bool); 12] = [
+    (-1, -1, 0, 0, true), (-1, -1, 1, 0, true), (-1, -1, 0, 1, true),
+    (1, -1, 0, 0, true), (1, -1, -1, 0, false), (1, -1, 0, 1, true),
+    (1, 1, 0, 0, false), (1, 1, 0, -1, true), (1, 1, -1, 0, false),
+    (-1, 1, 0, 0, false), (-1, 1, 1, 0, false), (-1, 1, 0, -1, false),
+];
+
+/**
+Finds and decodes Aztec codes from the binarized image.
+Bullseye centers are searched from every row and confirmed from the column,
+the rings of the bullseye give the position and the size of the modules.
+Parameters:
+image - binarized image.
+**/
+pub fn find_aztec_codes(image: &BitMatrix) -> Vec<Barcode> {
+    let mut found = Vec::new();
+    let mut visited = BitMatrix::new(image.width, image.height);
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for y in 0..image.height {
+        // Start and length of the runs, dark runs have even indices.
+        runs.clear();
+        let mut x = 0;
+        while x < image.width && !image.get(x, y) {
+            x += 1;
+        }
+        while x < image.width {
+            let start = x;
+            let color = image.get(x, y);
+            while x < image.width && image.get(x, y) == color {
+                x += 1;
+            }
+            runs.push((start, x - start));
+        }
+        for i in (0..runs.len().saturating_sub(BULLSEYE_RUNS - 1)).step_by(2) {
+            let window = &runs[i..i + BULLSEYE_RUNS];
+            let lengths: Vec<usize> = window.iter().map(|r| r.1).collect();
+            let module = match bullseye_module_size(&lengths) {
+                Some(module) => module,
+                None => continue,
+            };
+            let middle = window[BULLSEYE_RUNS / 2];
+            let center_x = middle.0 as f32 + middle.1 as f32 / 2.0;
+            let center_y = match cross_check_vertical(image, center_x as usize, y, module) {
+                Some(center_y) => center_y,
+                None => continue,
+            };
+            if let Some(text) = decode_bullseye(image, &mut visited, (center_x, center_y)) {
+                found.push(Barcode::new(Symbology::Aztec, text));
+            }
+        }
+    }
+    found
+}
+
+/**
+Module size if the runs have the same widths with half a module tolerance.
+Outermost dark runs can continue to the dark modules of the mode message, so only their minimum width is checked.
+**/
+fn bullseye_module_size(lengths: &[usize]) -> Option<f32> {
+    let inner = &lengths[1..lengths.len() - 1];
+    let total: usize = inner.iter().sum();
+    if total < inner.len() {
+        return None;
+    }
+    let module = total as f32 / inner.len() as f32;
+    let outer_ok = [lengths[0], lengths[lengths.len() - 1]].iter().all(|l| *l as f32 > module / 2.0);
+    if outer_ok && inner.iter().all(|l| (*l as f32 - module).abs() < module / 2.0) {Some(module)} else {None}
+}
+
+/**
+Counts the bullseye runs up and down from the center run of the row.
+Returns the center of the middle run in the column.
+Parameters:
+row_module - module size on the row, the size on the column may not differ much.
+**/
+fn cross_check_vertical(image: &BitMatrix, x: usize, y: usize, row_module: f32) -> Option<f32> {
+    let max_run = (row_module * 2.0) as usize;
+    let run_length = |start: i32, step: i32, dark: bool| {
+        let mut p = start;
+        while p >= 0 && (p as usize) < image.height && image.get(x, p as usize) == dark && p.abs_diff(start) as usize <= max_run {
+            p += step;
+        }
+        p.abs_diff(start) as usize
+    };
+    if !image.get(x, y) {
+        return None;
+    }
+    let mut lengths = [0_usize; BULLSEYE_RUNS];
+    let middle = BULLSEYE_RUNS / 2;
+    let up = run_length(y as i32, -1, true);
+    let down = run_length(y as i32, 1, true);
+    lengths[middle] = up + down - 1;
+    let (mut top, mut bottom) = (y as i32 - up as i32, y as i32 + down as i32);
+    for i in 1..=middle {
+        let dark = i % 2 == 0;
+        lengths[middle - i] = run_length(top, -1, dark);
+        top -= lengths[middle - i] as i32;
+        lengths[middle + i] = run_length(bottom, 1, dark);
+        bottom += lengths[middle + i] as i32;
+    }
+    let module = bullseye_module_size(&lengths)?;
+    if 5.0 * (module - row_module).abs() >= 2.0 * row_module {
+        return None;
+    }
+    Some(y as f32 - up as f32 + 1.0 + lengths[middle] as f32 / 2.0)
+}
+
+/**
+Finds the symbol around the bullseye center and decodes it.
+Second dark ring of the bullseye is filled to get the first estimate of the module grid,
+it is then made more accurate from the edges of the outer rings.
+**/
+fn decode_bullseye(image: &BitMatrix, visited: &mut BitMatrix, center: Point) -> Option<String> {
+    let y = center.1 as usize;
+    let mut x = center.0 as usize;
+    // Every run on the row from the center is one ring further.
+    for _ in 0..FIRST_RING {
+        let dark = image.get(x, y);
+        while x < image.width && image.get(x, y) == dark {
+            x += 1;
+        }
+        if x == image.width {
+            return None;
+        }
+    }
+    if visited.get(x, y) {
+        return None;
+    }
+    let area = fill_dark_area(image, visited, x, y);
+    let quad = hull_quadrilateral(&area.hull)?;
+    let r = FIRST_RING as f32 + 0.5;
+    // Hull corners are counterclockwise, the module coordinates go clockwise from the top left.
+    let transform = PerspectiveTransform::quadrilateral_to_quadrilateral(&[(-r, -r), (r, -r), (r, r), (-r, r)], &[quad[0], quad[3], quad[2], quad[1]]);
+    // Hull of the small ring is too rough for the edge search, the first fit is refined once more.
+    let transform = fit_bullseye(image, &transform, 4)?;
+    let transform = fit_bullseye(image, &transform, 4)?;
+    let compact = !is_full_range_bullseye(image, &transform);
+    let (transform, radius) = if compact {(transform, 5)} else {(fit_bullseye(image, &transform, 6)?, 7)};
+    let orientation = find_orientation(image, &transform, radius)?;
+    let module = |x: i32, y: i32| {
+        let (x, y) = orientation(x, y);
+        is_dark(image, transform.transform(x as f32, y as f32))
+    };
+    // Middle module of the full-range mode message sides is on the reference grid line.
+    let side_modules = if compact {7} else {10};
+    let module = &module;
+    let mode_bits: Vec<bool> = (0..4).flat_map(|side| (0..side_modules).map(move |i| {
+        let offset = if compact {i - 3} else {i - 5 + i / 5};
+        mode_message_module(module, side, offset, radius)
+    })).collect();
+    let (layers, data_codewords) = read_mode_message(&mode_bits, compact)?;
+    let size = symbol_size(compact, layers);
+    // Grid coordinates of the corners of the fitted ring, relative to the center module.
+    let center = (size / 2) as f32 + 0.5;
+    let r = radius as f32 - 1.5;
+    let corners = [(-r, -r), (r, -r), (r, r), (-r, r)];
+    let src = corners.map(|c| (c.0 + center, c.1 + center));
+    let dst = corners.map(|c| {
+        let (x, y) = orientation_f32(&orientation, c);
+        transform.transform(x, y)
+    });
+    // Edges of the small bullseye rings are jagged, so the errors of the rotation and the scale grow towards
+    // the outer layers. Corrections are tried until the error correction of the data accepts the symbol.
+    let middle = (dst.iter().map(|p| p.0).sum::<f32>() / 4.0, dst.iter().map(|p| p.1).sum::<f32>() / 4.0);
+    for scale in SCALE_CORRECTIONS.iter() {
+        for degrees in ROTATION_CORRECTIONS.iter() {
+            let (sin, cos) = degrees.to_radians().sin_cos();
+            let corrected = dst.map(|(x, y)| {
+                let (dx, dy) = ((x - middle.0) * scale, (y - middle.1) * scale);
+                (middle.0 + cos * dx - sin * dy, middle.1 + sin * dx + cos * dy)
+            });
+            let transform = PerspectiveTransform::quadrilateral_to_quadrilateral(&src, &corrected);
+            if let Some(text) = sample_grid(image, size, size, &transform).and_then(|grid| decode_aztec(&grid, compact, layers, data_codewords)) {
+                return Some(text);
+            }
+        }
+    }
+    None
+}
+
+/**
+Module of the mode message. Sides are read clockwise from the top, each of them clockwise.
+Parameters:
+module - module color at the upright coordinates relative to the center.
+side - 0 top, 1 right, 2 bottom, 3 left.
+offset - position on the side, 0 is the middle.
+radius - distance of the mode message ring from the center.
+**/
+fn mode_message_module(module: &dyn Fn(i32, i32) -> bool, side: usize, offset: i32, radius: i32) -> bool {
+    match side {
+        0 => module(offset, -radius),
+        1 => module(radius, offset),
+        2 => module(-offset, radius),
+        _ => module(-radius, -offset),
+    }
+}
+
+/**
+Transform fitted to the edges of the bullseye rings. The corners of every edge square are
+the intersections of the lines fitted to its sides, the corners of the outermost edge are then
+fitted to the middle lines of the rings, so that the errors of the single edges are averaged out.
+Parameters:
+transform - current estimate from the module coordinates relative to the center to the image.
+outer_ring - distance of the outermost dark ring from the center module.
+**/
+fn fit_bullseye(image: &BitMatrix, transform: &PerspectiveTransform, outer_ring: usize) -> Option<PerspectiveTransform> {
+    // Distances of the edges from the center, both edges of the inner dark rings and the inner edge of the outermost one.
+    let edges: Vec<f32> = (1..outer_ring).map(|e| e as f32 + 0.5).collect();
+    let mut fitted: Vec<(f32, [Point; 4])> = Vec::new();
+    for e in edges.iter() {
+        // Dark rings are at the even distances, the edge is searched from the middle of the nearest one.
+        let ring = 2.0 * (*e / 2.0).round();
+        fitted.push((*e, fit_edge_square(image, transform, *e, ring)?));
+    }
+    // Binarization can make the dark rings thicker, so the middle lines of the rings between the edges are used.
+    let fitted: Vec<(f32, [Point; 4])> = fitted.windows(2).map(|w| {
+        let mut corners = [(0.0, 0.0); 4];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            *corner = lerp(w[0].1[i], w[1].1[i], 0.5);
+        }
+        ((w[0].0 + w[1].0) / 2.0, corners)
+    }).collect();
+    // Corners move linearly with the distance from the center, fitted with the least squares.
+    let n = fitted.len() as f32;
+    let mean_e = fitted.iter().map(|f| f.0).sum::<f32>() / n;
+    let variance: f32 = fitted.iter().map(|f| (f.0 - mean_e).powi(2)).sum();
+    let outer = *edges.last()?;
+    let mut dst: [Point; 4] = [(0.0, 0.0); 4];
+    for (i, corner) in dst.iter_mut().enumerate() {
+        let mean = (fitted.iter().map(|f| f.1[i].0).sum::<f32>() / n, fitted.iter().map(|f| f.1[i].1).sum::<f32>() / n);
+        let slope = (
+            fitted.iter().map(|f| (f.0 - mean_e) * (f.1[i].0 - mean.0)).sum::<f32>() / variance,
+            fitted.iter().map(|f| (f.0 - mean_e) * (f.1[i].1 - mean.1)).sum::<f32>() / variance,
+        );
+        *corner = (mean.0 + slope.0 * (outer - mean_e), mean.1 + slope.1 * (outer - mean_e));
+    }
+    // Perspective can not be estimated from the small bullseye, it would only grow the errors of the outer layers.
+    let center = (dst.iter().map(|p| p.0).sum::<f32>() / 4.0, dst.iter().map(|p| p.1).sum::<f32>() / 4.0);
+    let half_x = ((dst[1].0 - dst[0].0 + dst[2].0 - dst[3].0) / 4.0, (dst[1].1 - dst[0].1 + dst[2].1 - dst[3].1) / 4.0);
+    let half_y = ((dst[3].0 - dst[0].0 + dst[2].0 - dst[1].0) / 4.0, (dst[3].1 - dst[0].1 + dst[2].1 - dst[1].1) / 4.0);
+    let dst = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .map(|(sx, sy): (f32, f32)| (center.0 + sx * half_x.0 + sy * half_y.0, center.1 + sx * half_x.1 + sy * half_y.1));
+    Some(PerspectiveTransform::quadrilateral_to_quadrilateral(&[(-outer, -outer), (outer, -outer), (outer, outer), (-outer, outer)], &dst))
+}
+
+/**
+Image corners of the square edge at the distance from the center, clockwise from the top left.
+The edge points are searched from the middle of the dark ring to the middle of the light ring
+next to it, a line is fitted to the points of every side and the corners are the intersections of the lines.
+Parameters:
+edge - distance of the edge from the center.
+ring - distance of the dark ring of the edge.
+**/
+fn fit_edge_square(image: &BitMatrix, transform: &PerspectiveTransform, edge: f32, ring: f32) -> Option<[Point; 4]> {
+    let light = 2.0 * edge - ring;
+    let normals = [(0.0, -1.0), (1.0, 0.0), (0.0, 1.0), (-1.0, 0.0)];
+    let mut lines = [((0.0, 0.0), (0.0, 0.0)); 4];
+    for (side, normal) in normals.iter().enumerate() {
+        let tangent = (-normal.1, normal.0);
+        let mut points: Vec<Point> = Vec::new();
+        // Corners are left out, the rings have different lengths there.
+        let mut s = -(edge - 0.75);
+        while s <= edge - 0.75 {
+            let at = |distance: f32| transform.transform(normal.0 * distance + tangent.0 * s, normal.1 * distance + tangent.1 * s);
+            if let Some(p) = find_edge(image, at(ring), at(light)) {
+                points.push(p);
+            }
+            s += 0.25;
+        }
+        if points.len() < MIN_EDGE_POINTS {
+            return None;
+        }
+        // Points next to the corners can hit the neighbouring side, the line is fitted again without them.
+        let line = fit_line(&points);
+        let mut residuals: Vec<f32> = points.iter().map(|p| line_distance(line, *p)).collect();
+        residuals.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let limit = 2.0 * residuals[residuals.len() / 2] + 0.5;
+        points.retain(|p| line_distance(line, *p) <= limit);
+        lines[side] = if points.len() >= MIN_EDGE_POINTS {fit_line(&points)} else {line};
+    }
+    let mut corners = [(0.0, 0.0); 4];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        // Top left corner is on the left and top sides.
+        *corner = intersect(lines[(i + 3) % 4], lines[i])?;
+    }
+    Some(corners)
+}
+
+///Point between the last dark and the first light pixel on the line from the dark point.
+fn find_edge(image: &BitMatrix, from: Point, to: Point) -> Option<Point> {
+    if !is_dark(image, from) {
+        return None;
+    }
+    let steps = ((to.0 - from.0).hypot(to.1 - from.1) * 4.0) as usize;
+    let mut previous = from;
+    for i in 1..=steps {
+        let p = lerp(from, to, i as f32 / steps as f32);
+        if !is_dark(image, p) {
+            return Some(lerp(previous, p, 0.5));
+        }
+        previous = p;
+    }
+    None
+}
+
+///Least squares line through the points as the mean point and the direction.
+fn fit_line(points: &[Point]) -> (Point, Point) {
+    let n = points.len() as f32;
+    let mean = (points.iter().map(|p| p.0).sum::<f32>() / n, points.iter().map(|p| p.1).sum::<f32>() / n);
+    let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+    for p in points.iter() {
+        let (dx, dy) = (p.0 - mean.0, p.1 - mean.1);
+        sxx += dx * dx;
+        sxy += dx * dy;
+        syy += dy * dy;
+    }
+    let angle = 0.5 * (2.0 * sxy).atan2(sxx - syy);
+    (mean, (angle.cos(), angle.sin()))
+}
+
+///Distance of the point from the line given as a point and a unit direction.
+fn line_distance(line: (Point, Point), p: Point) -> f32 {
+    let ((x, y), (dx, dy)) = line;
+    ((p.0 - x) * dy - (p.1 - y) * dx).abs()
+}
+
+fn intersect(a: (Point, Point), b: (Point, Point)) -> Option<Point> {
+    let ((p, d), (q, e)) = (a, b);
+    let denominator = d.0 * e.1 - d.1 * e.0;
+    if denominator.abs() < 1e-3 {
+        return None;
+    }
+    let t = ((q.0 - p.0) * e.1 - (q.1 - p.1) * e.0) / denominator;
+    Some((p.0 + d.0 * t, p.1 + d.1 * t))
+}
+
+/**
+Full-range bullseye has a light ring and a dark ring outside of the rings of the compact one.
+In the compact symbol these are the mode message and the data.
+**/
+fn is_full_range_bullseye(image: &BitMatrix, transform: &PerspectiveTransform) -> bool {
+    let mut matches = 0;
+    let mut total = 0;
+    for &(ring, dark) in [(5, false), (6, true)].iter() {
+        for i in -ring..ring {
+            for (x, y) in [(i, -ring), (ring, i), (-i, ring), (-ring, -i)].iter() {
+                let (px, py) = transform.transform(*x as f32, *y as f32);
+                if is_dark(image, (px, py)) == dark {
+                    matches += 1;
+                }
+                total += 1;
+            }
+        }
+    }
+    matches * 10 >= total * 9
+}
+
+/**
+Finds the rotation and mirroring of the symbol from the orientation marks.
+Returns the function that maps the upright module coordinates relative to the center to the
+coordinates in the image orientation.
+**/
+fn find_orientation(image: &BitMatrix, transform: &PerspectiveTransform, radius: i32) -> Option<impl Fn(i32, i32) -> (i32, i32)> {
+    let orient = |variant: usize, x: i32, y: i32| {
+        let (mut x, mut y) = if variant >= 4 {(-x, y)} else {(x, y)};
+        for _ in 0..variant % 4 {
+            let rotated = (-y, x);
+            x = rotated.0;
+            y = rotated.1;
+        }
+        (x, y)
+    };
+    let matches = |variant: usize| ORIENTATION_MARKS.iter().filter(|(sx, sy, dx, dy, dark)| {
+        let (x, y) = orient(variant, sx * radius + dx, sy * radius + dy);
+        is_dark(image, transform.transform(x as f32, y as f32)) == *dark
+    }).count();
+    let best = (0..8).max_by_key(|v| matches(*v))?;
+    if matches(best) < MIN_ORIENTATION_MATCHES {
+        return None;
+    }
+    Some(move |x, y| orient(best, x, y))
+}
+
+///Orientation function applied to a point with fractional coordinates.
+fn orientation_f32(orientation: &dyn Fn(i32, i32) -> (i32, i32), p: Point) -> Point {
+    // The mapping is linear, so it is applied to the unit vectors.
+    let (ax, ay) = orientation(1, 0);
+    let (bx, by) = orientation(0, 1);
+    (ax as f32 * p.0 + bx as f32 * p.1, ay as f32 * p.0 + by as f32 * p.1)
+}
+
+/// Compact symbol with 3 layers of "HELLOworld123" in the upper, lower and digit modes, "1" is a dark module.
+#[cfg(test)]
+pub(crate) const COMPACT_MODULES: [&str; 23] = [
+    "01011000000000100000110", "00001001010011100011011", "10100100000000011010110", "01000011110010101001100",
+    "10000111000011000000111", "01001101111000001010100", "10011011100010001000001", "10011011111111111010110",
+    "11001111000000010100111", "01000111011111010010011", "10101111010001011001001", "00100011010101010101001",
+    "01001111010001011011101", "11111001011111011010011", "00000011000000011000111", "11011001111111111000101",
+    "00111000011100000111011", "01000110100101110000101", "00111010100011001011010", "00011001110111010100110",
+    "10010010010100010101100", "10110011001101100100010", "01100111101000000011110",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::COMPACT_MODULES;
+    use crate::decoder_options::DecoderOptions;
+    use crate::symbology::Symbology;
+    use crate::test_images::read_modules;
+
+    /// Full-range symbol with 4 layers of the same text.
+    const FULL_MODULES: [&str; 31] = [
+        "0100001000000000001001000111011", "0001100100100001111000100101000", "1010001100011110100001111000010",
+        "0101011000111111011101111011100", "1001101101110000011000011010100", "0110110011000101101010100101100",
+        "1001010111110100110100101010101", "1010010011100011001110011010011", "1110011111000110000000111010110",
+        "0110011011111111111111100101100", "1000101111000000000001011111110", "0000101101011111111101000001000",
+        "0111111011010000000101111110011", "1111011111010111110101001110101", "0001001011010100010101001010110",
+        "0101010101010101010101010101010", "1100001001010100010101000001011", "0011010101010111110101111100111",
+        "0110100011010000000101011011110", "0001000101011111111101100100110", "0001011011000000000001101001011",
+        "1001101101111111111111110010100", "0100000000001010000010010101110", "1001010111000011101000010000110",
+        "1111001111011110101000100011000", "0111010011000011001000010110011", "0000101101100110101100110101001",
+        "1010011110011011011100110000001", "1111111101110000011011100010110", "1001101101100111011100001111000",
+        "1101000010110000001001000000000",
+    ];
+
+    fn aztec_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::Aztec])
+    }
+
+    #[test]
+    fn reads_aztec() {
+        assert_eq!(read_modules(&COMPACT_MODULES, 0.0, &aztec_options()), vec!["Aztec HELLOworld123"]);
+        assert_eq!(read_modules(&FULL_MODULES, 0.0, &aztec_options()), vec!["Aztec HELLOworld123"]);
+    }
+
+    #[test]
+    fn corrects_errors() {
+        let mut rows : Vec<String> = COMPACT_MODULES.iter().map(|r| r.to_string()).collect();
+        // Two data modules of the outer layer are flipped.
+        rows[0].replace_range(0..2, "10");
+        let rows : Vec<&str> = rows.iter().map(|r| r.as_str()).collect();
+        assert_eq!(read_modules(&rows, 0.0, &aztec_options()), vec!["Aztec HELLOworld123"]);
+    }
+}
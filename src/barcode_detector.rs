@@ -1,163 +1,554 @@
-use std::cmp::max;
-use crate::BarcodeBarArray;
-use crate::color_line_helpers::{find_crossings_from_average, ColorLine};
+use std::cmp::{max, min};
+use log::debug;
+use crate::aztec_reader::find_aztec_codes;
+use crate::barcode_translate::translate_bar_code;
+use crate::bit_matrix::binarize_image;
+use crate::code128_reader::find_code128;
+use crate::code39_reader::find_code39;
+use crate::code93_reader::find_code93;
+use crate::databar_reader::{combine_databar_parts, read_databar_parts, DataBarPart, DataBarRows};
+use crate::decoder_options::{DecoderOptions, Luminance};
+use crate::error::{Error, Result};
+use crate::datamatrix_reader::find_datamatrix_codes;
+use crate::codabar_reader::find_codabar;
+use crate::itf_reader::find_itf;
+use crate::pixel_sources::{GrayImage, LuminanceSource};
+use crate::qr_reader::find_qr_codes;
+use crate::dark_area::Point;
+use crate::color_line_helpers::{crossing_widths, find_crossings_from_average, ColorLine};
+use crate::symbology::{Barcode, ScanPass, Symbology};
 
+const EAN13_HALF_DIGITS: usize = 6;
+const EAN8_HALF_DIGITS: usize = 4;
+
+/// Quiet zone of EAN-8 and UPC-E in modules. The standard asks for 7, tightly cropped codes have a bit less.
+const EAN_QUIET_ZONE_MODULES: f32 = 5.0;
+
+/// Smallest accepted width and height, the smallest Data Matrix symbol is 10 X 10 modules.
+const MIN_IMAGE_SIZE: u32 = 10;
 
 /**Implement PixelValue for the image data source.
-    x - pixel position on vertical axis
-    y - pixel position on horizontal axis
-    channel - color channel. red, green on blue.
-    w - width of the image
+    x - pixel position on the horizontal axis, the column number
+    y - pixel position on the vertical axis, the row number
+    channel - color channel. red, green or blue.
+channel_count() is the number of the color channels, the default is 4 for RGBA images.
+image_size() is the width and the height of the pixels, the dimensions of the detection may not be bigger.
+The default None leaves the dimensions unchecked.
+read_line() fills the buffer with the pixels from the start on, one step apart. Rows are read with the step (1, 0)
+and columns with (0, 1). The default reads the pixels one by one, sources with the pixels in memory can copy them directly.
 **/
 pub trait PixelValue {
-    fn get_pixel_value(&self, x: u32, y:u32, channel: usize, w:usize) -> u8;
+    fn get_pixel_value(&self, x: u32, y:u32, channel: usize) -> u8;
+
+    fn channel_count(&self) -> usize {
+        4
+    }
+
+    fn image_size(&self) -> Option<(u32,u32)> {
+        None
+    }
+
+    fn read_line(&self, start: (u32,u32), step: (i32,i32), channel: usize, buf: &mut [u8]) {
+        let (mut x, mut y) = (start.0 as i64, start.1 as i64);
+        for value in buf.iter_mut() {
+            *value = self.get_pixel_value(x as u32, y as u32, channel);
+            x += step.0 as i64;
+            y += step.1 as i64;
+        }
+    }
+}
+
+/**
+Bar widths of the EAN-13, EAN-8 or UPC-E barcode found from a scan line, before the digits are translated.
+    row - position of the scan line, the row number for the rows.
+    deg - angle of the scan line in degrees, 180 is added for the lines read from the end to the start.
+    start, end - indexes of the first and the last pixel of the barcode on the line.
+    start_point, end_point - image coordinates of the first and the last pixel.
+    full - both halves were read from the same line, otherwise only one half was read or the halves come from different lines.
+    left, right - widths of the bars and spaces of the digits in modules, UPC-E has only the left digits.
+    addon - widths of the EAN-2 or EAN-5 add-on digits, zeros if there is no add-on.
+    lines - number of the scan lines the same bars were read from.
+**/
+#[derive(Clone, Copy, Debug)]
+pub struct BarcodeBars {
+    pub row: usize,
+    pub deg: u16,
+    pub start: usize,
+    pub end: usize,
+    pub start_point: Point,
+    pub end_point: Point,
+    pub full: bool,
+    pub symbology: Symbology,
+    pub left: [[u8; 4]; 6],
+    pub right: [[u8; 4]; 6],
+    pub addon: [[u8; 4]; 5],
+    pub lines: u32,
+}
+
+impl BarcodeBars {
+    ///Creates bars without the digit widths, start and end are the indexes on the scan line.
+    fn new(color_line: &ColorLine, symbology: Symbology, start: usize, end: usize, full: bool) -> BarcodeBars {
+        BarcodeBars {
+            row: color_line.pos as usize,
+            deg: color_line.deg,
+            start,
+            end,
+            start_point: color_line.point(start),
+            end_point: color_line.point(end),
+            full,
+            symbology,
+            left: [[0; 4]; 6],
+            right: [[0; 4]; 6],
+            addon: [[0; 4]; 5],
+            lines: 1,
+        }
+    }
+
+    ///Widths of the bars and spaces of all digits in modules, the left digits first. Add-on is not included.
+    pub fn module_widths(&self) -> Vec<u8> {
+        let half = ean_half_digits(self.symbology);
+        let right = if self.symbology == Symbology::UpcE {0} else {half};
+        self.left[..half].iter().chain(self.right[..right].iter()).flatten().cloned().collect()
+    }
 }
 
 /**
-Detects and parses barcode(s) from images.
+Detects and parses the linear barcode(s) from the image rows, each row is read in both directions.
+Columns, rotated lines and 2D symbols are searched for by process_image_with_options(), this is the fastest setting.
+Returns an error if the image is smaller than 10 pixels on either side, if dim is bigger than the image
+or if the image does not have the color channel.
 Parameters:
 img - object with type that has implemented PixelValue trait.
 dim - width and height of the image
 color_channel - color channel number that is provided to get_pixel_value()
 **/
-pub fn process_image_by_rows(img: &dyn PixelValue, dim: (u32,u32), color_channel: usize) -> Vec<BarcodeBarArray> {
-    let step = calculate_row_step(dim.1);
-    println!("Dimensions {} X {} STEP: {}", dim.0, dim.1, step);
-    let mut y = 0;
-
-    let row_slice_size = max(30, max(dim.0, dim.1) / 40) as usize;
-    let big_image = row_slice_size > 40;
-
-    let mut found_bar_codes : Vec<BarcodeBarArray> = Vec::new();
-    let mut partial_bar_codes : Vec<BarcodeBarArray> = Vec::new();
-
-    while y < dim.1 {
-        let len = dim.0;
-        let mut line_sum = 0;
-        let mut line = ColorLine {
-                avg: 0,
-                min: 255,
-                max: 0,
-                deg: 0,
-                pos: y,
-                len,
-                values: vec![0 as u8; len as usize],
-                avg_loc : Vec::with_capacity((len as usize /row_slice_size) + 1),
-                min_loc : Vec::with_capacity((len as usize/row_slice_size) + 1),
-                max_loc : Vec::with_capacity((len as usize/row_slice_size) + 1),
-                slice_size: row_slice_size
+pub fn process_image_by_rows(img: &dyn PixelValue, dim: (u32,u32), color_channel: usize) -> Result<Vec<Barcode>> {
+    let options = DecoderOptions::default().scan_angles(&[0]).luminance(Luminance::Channel(color_channel));
+    process_image_with_options(img, dim, &options)
+}
+
+/**
+Detects and parses barcode(s) from images with the given options. By default the linear barcodes are searched from
+the rows, the columns and the lines at every 15 degrees.
+Gray values are read by the luminance of the options, if nothing is found and try_channels is set,
+the red, green and blue channels are tried in turn.
+Only the enabled symbologies are returned, at most max_results of them.
+**/
+pub fn process_image_with_options(img: &dyn PixelValue, dim: (u32,u32), options: &DecoderOptions) -> Result<Vec<Barcode>> {
+    process_image(img, dim, options, false)
+}
+
+/**
+Detects the barcodes like process_image_with_options(), the scan lines of each angle are read on the rayon threads.
+The image is read to memory first and the lines are read from there, so the image does not have to be Sync.
+Results are the same as without the threads.
+**/
+#[cfg(feature = "parallel")]
+pub fn process_image_parallel(img: &dyn PixelValue, dim: (u32,u32), options: &DecoderOptions) -> Result<Vec<Barcode>> {
+    process_image(img, dim, options, true)
+}
+
+///Detects the barcodes with the luminance of the options and then with the channels, parallel reads the lines on the threads.
+fn process_image(img: &dyn PixelValue, dim: (u32,u32), options: &DecoderOptions, parallel: bool) -> Result<Vec<Barcode>> {
+    if dim.0 < MIN_IMAGE_SIZE || dim.1 < MIN_IMAGE_SIZE {
+        return Err(Error::ImageTooSmall(dim.0, dim.1));
+    }
+    if let Some(size) = img.image_size() {
+        if dim.0 > size.0 || dim.1 > size.1 {
+            return Err(Error::InvalidBuffer(format!("dimensions {} X {} are bigger than the image {} X {}", dim.0, dim.1, size.0, size.1)));
+        }
+    }
+    let mut barcodes = find_barcodes(img, dim, options.luminance, options, parallel)?;
+    if barcodes.is_empty() && options.try_channels {
+        // Red ink disappears in the red channel, but it is dark in the others.
+        for channel in 0..min(3, img.channel_count()) {
+            if options.luminance == Luminance::Channel(channel) {
+                continue;
+            }
+            barcodes = find_barcodes(img, dim, Luminance::Channel(channel), options, parallel)?;
+            if !barcodes.is_empty() {
+                break;
+            }
+        }
+    }
+    Ok(barcodes)
+}
+
+///Detects the barcodes from the gray values of the image, read by the luminance.
+fn find_barcodes(img: &dyn PixelValue, dim: (u32,u32), luminance: Luminance, options: &DecoderOptions, parallel: bool) -> Result<Vec<Barcode>> {
+    if let Luminance::Channel(channel) = luminance {
+        if channel >= img.channel_count() {
+            return Err(Error::InvalidChannel(channel, img.channel_count()));
+        }
+    }
+    let gray = LuminanceSource::new(img, luminance);
+    let img: &dyn PixelValue = &gray;
+    let color_channel = 0;
+    let step = options.line_step(dim.1);
+    let row_slice_size = options.line_slice_size(dim);
+    let big_image = row_slice_size > options.big_image_slice_size;
+    debug!("Dimensions {} X {} step {} slice size {} big image {}", dim.0, dim.1, step, row_slice_size, big_image);
+    let find_ean = options.any_enabled(&[Symbology::Ean13, Symbology::Ean8, Symbology::UpcE]);
+    let find_2d = options.any_enabled(&[Symbology::QrCode, Symbology::DataMatrix, Symbology::Aztec]);
+    let angles = options.line_angles();
+
+    // Rotated lines and 2D symbols need all of the pixels, the image is read once instead of pixel by pixel.
+    // The threads read the lines from memory too, the image source might not be Sync.
+    let gray_image = if parallel || find_2d || angles.iter().any(|deg| deg % 90 != 0) {
+        Some(GrayImage::read(img, dim, color_channel))
+    } else {
+        None
+    };
+    let lines_img : &dyn PixelValue = match &gray_image {
+        Some(gray_image) => gray_image,
+        None => img,
+    };
+
+    let mut found_bar_codes : Vec<BarcodeBars> = Vec::new();
+    let mut decoded_bar_codes : Vec<Barcode> = Vec::new();
+
+    // Rows and columns are scanned first, rotated barcodes are found from the lines at the other angles.
+    for deg in angles {
+        let pass = ScanPass::from_deg(deg);
+        let (width, line_step) = match pass {
+            ScanPass::Columns => (dim.0 - 1, options.line_step(dim.0)),
+            _ => (scan_width(dim, deg), step),
+        };
+        let positions : Vec<u32> = (0..=width).step_by(line_step as usize).collect();
+        let scans = scan_lines(positions, lines_img, gray_image.as_ref(), parallel, |lines_img, gray_image, pos| {
+            let line = match (pass, gray_image) {
+                (ScanPass::Rows, _) => read_row(lines_img, dim, color_channel, pos, row_slice_size),
+                (ScanPass::Columns, _) => read_column(lines_img, dim, color_channel, pos, row_slice_size),
+                // Lines near the corners are too short for any barcode.
+                (_, Some(gray_image)) => read_scan_line(gray_image, deg, pos, row_slice_size)?,
+                (_, None) => return None,
             };
-        let mut slc = 0;
-        let mut slice_vals = (255,0,0);
-        for x in 0..len {
-            let px_code = img.get_pixel_value(x, y, color_channel,len as usize);
-            line.values[x as usize] = px_code;
-            slice_vals.2 += px_code as usize;
-            line_sum += px_code as usize;
-            if slice_vals.0 > px_code {
-                slice_vals.0 = px_code;
-                if line.min > px_code {
-                    line.min = px_code;
+            Some(scan_line(line, big_image, find_ean, options))
+        });
+        // Parts of the codes are combined only with the previous lines of the same angle, in the order of the lines.
+        let mut partial_bar_codes : Vec<BarcodeBars> = Vec::new();
+        // Halves of the stacked DataBar are paired by their positions, separately for both directions.
+        let mut databar_rows = DataBarRows::default();
+        let mut reversed_databar_rows = DataBarRows::default();
+        // Codes of the previous line, with true if they were added.
+        let mut previous_codes : Vec<(Barcode, bool)> = Vec::new();
+        let mut previous_bar_code : Option<BarcodeBars> = None;
+        for scan in scans {
+            let LineScan { line, mut codes, databar_parts, reversed_databar_parts, bars } = scan;
+            let pos = line.pos;
+            let forward = combine_databar_parts(databar_parts, &mut databar_rows).into_iter().map(|code| (code, line.deg));
+            let reversed = combine_databar_parts(reversed_databar_parts, &mut reversed_databar_rows).into_iter().map(|code| (code, line.deg + 180));
+            for (mut code, deg) in forward.chain(reversed) {
+                if !codes.iter().any(|c| c.symbology == code.symbology && c.text == code.text) {
+                    code.set_scan_line(pos, deg);
+                    codes.push(code);
                 }
             }
-            if slice_vals.1 < px_code {
-                slice_vals.1 = px_code;
-                if line.max < px_code {
-                    line.max = px_code;
+            // Rotated lines can cross only a corner of the barcode, so their codes have to be read from two neighbouring lines.
+            let mut line_codes = Vec::with_capacity(codes.len());
+            for code in codes {
+                let previous = previous_codes.iter().find(|(c, _)| c.has_same_content(&code));
+                let lines = match (pass, previous) {
+                    (ScanPass::Rotated, None) => 0,
+                    // The previous line was not counted when it was read.
+                    (ScanPass::Rotated, Some((_, false))) => 2,
+                    _ => 1,
+                };
+                if lines > 0 {
+                    add_line_bar_code(&mut decoded_bar_codes, code.clone(), lines);
+                } else {
+                    debug!("{} on line {} rejected: not found from the previous line", code, pos);
+                }
+                line_codes.push((code, lines > 0));
+            }
+            previous_codes = line_codes;
+            let previous = previous_bar_code;
+            previous_bar_code = bars;
+            if let Some(bar_code) = bars {
+                if bar_code.full {
+                    let confirmed = pass != ScanPass::Rotated || previous.is_some_and(|p| p.full && are_barcodes_same(&p, &bar_code));
+                    if !confirmed {
+                        debug!("{} bars on line {} deg {} rejected: not found from the previous line", bar_code.symbology, pos, deg);
+                        continue;
+                    }
+                    let mut add = true;
+                    if let Some(last) = found_bar_codes.last_mut() {
+                        add = !are_barcodes_same(last, &bar_code);
+                        if !add {
+                            last.lines += 1;
+                            // Add-on might be visible only on some of the rows.
+                            if last.addon[0][0] == 0 {
+                                last.addon = bar_code.addon;
+                            }
+                        }
+                    }
+                    if add {
+                        found_bar_codes.push(BarcodeBars {lines: if pass == ScanPass::Rotated {2} else {1}, ..bar_code});
+                    }
+                } else if pass != ScanPass::Rotated {
+                    // Positions on the rotated lines can not be compared, the lines start from different image edges.
+                    if !partial_bar_codes.is_empty(){
+                        let find_full = check_partial_bar_code(&mut partial_bar_codes, &found_bar_codes, &bar_code);
+                        if let Some(code) = find_full {
+                            found_bar_codes.push(code);
+                        };
+                    }
+                    partial_bar_codes.push(bar_code);
                 }
             }
+        }
+    }
 
-            slc += 1;
-            if slc >= row_slice_size {
-                line.min_loc.push(slice_vals.0);
-                line.max_loc.push(slice_vals.1);
-                line.avg_loc.push((slice_vals.2 / slc as usize) as u8);
-                slice_vals = (255,0,0);
-                slc = 0;
+    // 2D symbols are searched from the whole binarized image instead of the single rows.
+    if let (true, Some(gray_image)) = (find_2d, &gray_image) {
+        let bit_matrix = binarize_image(gray_image);
+        if options.is_enabled(Symbology::QrCode) {
+            for code in find_qr_codes(&bit_matrix) {
+                add_decoded_bar_code(&mut decoded_bar_codes, code);
+            }
+        }
+        if options.is_enabled(Symbology::DataMatrix) {
+            for code in find_datamatrix_codes(&bit_matrix) {
+                add_decoded_bar_code(&mut decoded_bar_codes, code);
             }
+        }
+        if options.is_enabled(Symbology::Aztec) {
+            for code in find_aztec_codes(&bit_matrix) {
+                add_decoded_bar_code(&mut decoded_bar_codes, code);
+            }
+        }
+    }
+
+    let mut ean_bar_codes : Vec<Barcode> = Vec::new();
+    for bar_code in found_bar_codes.iter() {
+        if let Some(code) = translate_bar_code(bar_code) {
+            add_decoded_bar_code(&mut ean_bar_codes, code);
+        }
+    }
+    ean_bar_codes.append(&mut decoded_bar_codes);
+    // Decoders of the similar symbologies report both of them, like Code 128 and GS1-128.
+    // Linear codes read from one line only can be left out by the confidence limit.
+    ean_bar_codes.retain(|c| options.is_enabled(c.symbology) && c.confidence >= options.min_confidence);
+    if let Some(max_results) = options.max_results {
+        ean_bar_codes.truncate(max_results);
+    }
+    return Ok(ean_bar_codes);
+}
+
+///Codes and bars read from one scan line, before they are combined with the other lines of the same angle.
+struct LineScan {
+    line: ColorLine,
+    codes: Vec<Barcode>,
+    databar_parts: Vec<DataBarPart>,
+    reversed_databar_parts: Vec<DataBarPart>,
+    bars: Option<BarcodeBars>,
+}
 
+/**
+Reads the codes from the scan line. Every line is read on its own, so the lines can be read in parallel.
+The line is also read from the end to the start, the linear decoders read the bars only in one direction.
+Parameters:
+line - pixel values of the scan line.
+big_image - image slices are big, crossings get a wider buffer.
+find_ean - EAN or UPC barcodes are searched for.
+options - symbologies and thresholds of the detection.
+**/
+fn scan_line(line: ColorLine, big_image: bool, find_ean: bool, options: &DecoderOptions) -> LineScan {
+    let a = find_crossings_from_average(&line, big_image, options);
+    let diffs = crossing_widths(&a.1);
+    debug!("{} line {} deg {}: {} pixels, {} crossings", ScanPass::from_deg(line.deg), line.pos, line.deg, line.len, a.1.len());
+    let find_databar = options.any_enabled(&[Symbology::DataBar, Symbology::DataBarExpanded]);
+    let mut codes = find_line_codes(&line, &a, &diffs, options);
+    let databar_parts = if find_databar {read_databar_parts(&line, &a, &diffs)} else {Vec::new()};
+    let mut bars = if find_ean {find_bar_code(&line, &a, &diffs, big_image, options.width_tolerance)} else {None};
+
+    let reversed = line.reversed();
+    let ra = find_crossings_from_average(&reversed, big_image, options);
+    let rdiffs = crossing_widths(&ra.1);
+    // Code read in both directions counts as one read of the line.
+    for code in find_line_codes(&reversed, &ra, &rdiffs, options) {
+        if !codes.iter().any(|c| c.symbology == code.symbology && c.text == code.text) {
+            codes.push(code);
         }
-        if slc > 0 {
-            line.min_loc.push(slice_vals.0);
-            line.max_loc.push(slice_vals.1);
-            line.avg_loc.push((slice_vals.2 / slc as usize) as u8);
+    }
+    let reversed_databar_parts = if find_databar {read_databar_parts(&reversed, &ra, &rdiffs)} else {Vec::new()};
+    // EAN-13 and EAN-8 are also translated in reverse, but UPC-E has a different end guard
+    // and the add-on of the upside down code is before it.
+    if find_ean && !bars.is_some_and(|b| b.full && b.addon[0][0] != 0) {
+        let reversed_bars = find_bar_code(&reversed, &ra, &rdiffs, big_image, options.width_tolerance);
+        if reversed_bars.is_some_and(|r| r.full && (!bars.is_some_and(|b| b.full) || r.addon[0][0] != 0)) {
+            bars = reversed_bars;
         }
+    }
+    LineScan { line, codes, databar_parts, reversed_databar_parts, bars }
+}
 
-        line.avg = (line_sum / (len) as usize) as u8;
-        let a = find_crossings_from_average(&line, big_image);
-        let bar_code = find_bar_code(&line,&a, big_image);
-        if bar_code.0[3] > 0 {
-            //if full bar code
-            if bar_code.0[4] == 2 {
-                let mut add = true;
-                if found_bar_codes.len() > 0 {
-                    add = !are_barcodes_same(&found_bar_codes.last().unwrap(), &bar_code);
-                }
-                if add {
-                    found_bar_codes.push(bar_code);
-                }
-            } else {
-                if partial_bar_codes.len() > 0{
-                    let find_full = check_partial_bar_code(&mut partial_bar_codes, &found_bar_codes, &bar_code);
-                    match find_full {
-                        Some(code) =>  {
-                            found_bar_codes.push(code);
-                        },
-                        None => {}
-                    };
-                }
-                partial_bar_codes.push(bar_code);
+///Decodes the codes of the other linear symbologies than EAN and DataBar from the line, every code has the line set.
+fn find_line_codes(line: &ColorLine, a: &(bool,Vec<usize>), diffs: &[usize], options: &DecoderOptions) -> Vec<Barcode> {
+    let mut codes = Vec::new();
+    if options.any_enabled(&[Symbology::Code128, Symbology::Gs1128]) {
+        codes.append(&mut find_code128(line, a, diffs));
+    }
+    if options.any_enabled(&[Symbology::Code39, Symbology::Code39Extended]) {
+        codes.append(&mut find_code39(line, a, diffs, options.code39_check_digit, options.code39_full_ascii));
+    }
+    if options.any_enabled(&[Symbology::Code93, Symbology::Code93Extended]) {
+        codes.append(&mut find_code93(line, a, diffs));
+    }
+    if options.any_enabled(&[Symbology::Itf, Symbology::Itf14]) {
+        codes.append(&mut find_itf(line, a, diffs, options.itf_check_digit));
+    }
+    if options.is_enabled(Symbology::Codabar) {
+        codes.append(&mut find_codabar(line, a, diffs, options.codabar_start_stop, options.codabar_check_char));
+    }
+    for code in codes.iter_mut() {
+        code.set_scan_line(line.pos, line.deg);
+    }
+    codes
+}
+
+/**
+Reads the scan lines at the positions in their order, lines near the corners may be left out.
+With parallel the lines are read from the gray image on the rayon threads, otherwise one after another.
+Parameters:
+lines_img - image the rows and the columns are read from.
+gray_image - image the rotated lines are read from, if it is read.
+scan - reads the line at the position from the images.
+**/
+#[cfg_attr(not(feature = "parallel"), allow(unused_variables))]
+fn scan_lines<F>(positions: Vec<u32>, lines_img: &dyn PixelValue, gray_image: Option<&GrayImage>, parallel: bool, scan: F) -> Vec<LineScan>
+    where F: Fn(&dyn PixelValue, Option<&GrayImage>, u32) -> Option<LineScan> + Sync {
+    #[cfg(feature = "parallel")]
+    if let (true, Some(gray_image)) = (parallel, gray_image) {
+        use rayon::prelude::*;
+        return positions.into_par_iter().filter_map(|pos| scan(gray_image, Some(gray_image), pos)).collect();
+    }
+    positions.into_iter().filter_map(|pos| scan(lines_img, gray_image, pos)).collect()
+}
+
+/**
+Adds barcode to the list, if the same code is not found from previous lines. The angle it was first found at is kept.
+Add-on might be visible only on some of the lines, the code without it is the same code.
+**/
+fn add_decoded_bar_code(codes: &mut Vec<Barcode>, code: Barcode) {
+    let same = codes.iter_mut().find(|c| c.has_same_content(&code)
+        || (c.symbology == code.symbology && c.text == code.text && (c.addon.is_none() || code.addon.is_none())));
+    match same {
+        Some(found) => {
+            found.add_confidence(code.confidence);
+            if found.addon.is_none() {
+                found.addon = code.addon;
             }
-        }
-        y += step;
+        },
+        None => codes.push(code),
     }
+}
+
+///Adds barcode that was found from the number of scan lines, the line is set by the decoding.
+fn add_line_bar_code(codes: &mut Vec<Barcode>, mut code: Barcode, lines: u32) {
+    code.set_line_count(lines);
+    debug!("Found {}", code);
+    add_decoded_bar_code(codes, code);
+}
 
-    return found_bar_codes;
+///Distance between the first and the last scan line at the angle, the lines are perpendicular to this direction.
+fn scan_width(dim: (u32,u32), deg: u16) -> u32 {
+    let (sin, cos) = (deg as f32).to_radians().sin_cos();
+    ((dim.0 - 1) as f32 * sin.abs() + (dim.1 - 1) as f32 * cos.abs()) as u32
 }
 
+///Reads the pixel row from the left to the right, as the line at 0 degrees. Position of the line is the row number.
+fn read_row(img: &dyn PixelValue, dim: (u32,u32), color_channel: usize, y: u32, slice_size: usize) -> ColorLine {
+    let mut values = vec![0; dim.0 as usize];
+    img.read_line((0, y), (1, 0), color_channel, &mut values);
+    ColorLine::from_values(values, y, 0, (0.0, y as f32), slice_size)
+}
+
+///Reads the pixel column from the top to the bottom, as the line at 90 degrees. Position of the line is the column number.
+fn read_column(img: &dyn PixelValue, dim: (u32,u32), color_channel: usize, x: u32, slice_size: usize) -> ColorLine {
+    let mut values = vec![0; dim.1 as usize];
+    img.read_line((x, 0), (0, 1), color_channel, &mut values);
+    ColorLine::from_values(values, x, 90, (x as f32, 0.0), slice_size)
+}
+
+/**
+Reads the pixel line at the angle from the gray image, the pixels between the sample points are interpolated.
+Returns None if the part of the line inside the image is shorter than one slice.
+Parameters:
+deg - angle of the line in degrees, clockwise from the image rows.
+pos - distance from the first line, that goes through the corner of the image. Row number for 0 degrees.
+slice_size - number of the pixels in one slice of the ColorLine.
+**/
+fn read_scan_line(gray: &GrayImage, deg: u16, pos: u32, slice_size: usize) -> Option<ColorLine> {
+    let dim = (gray.width, gray.height);
+    let (sin, cos) = (deg as f32).to_radians().sin_cos();
+    let last = ((dim.0 - 1) as f32, (dim.1 - 1) as f32);
+    // Point of the line that is nearest to the image center, the line goes in the direction (cos, sin).
+    let offset = pos as f32 - scan_width(dim, deg) as f32 / 2.0;
+    let base = (last.0 / 2.0 - sin * offset, last.1 / 2.0 + cos * offset);
+    let (mut start, mut end) = (f32::MIN, f32::MAX);
+    for (b, d, l) in [(base.0, cos, last.0), (base.1, sin, last.1)].iter() {
+        if d.abs() < f32::EPSILON {
+            continue;
+        }
+        let (t0, t1) = (-b / d, (l - b) / d);
+        start = start.max(t0.min(t1));
+        end = end.min(t0.max(t1));
+    }
+    if end - start < slice_size as f32 {
+        return None;
+    }
+    let values = (0..=(end - start) as u32).map(|i| {
+        let t = start + i as f32;
+        gray.interpolated((base.0 + cos * t, base.1 + sin * t))
+    }).collect();
+    let origin = (base.0 + cos * start, base.1 + sin * start);
+    Some(ColorLine::from_values(values, pos, deg, origin, slice_size))
+}
 
-fn check_partial_bar_code(partial_bar_codes : &mut Vec<BarcodeBarArray>, full_codes : &Vec<BarcodeBarArray>,bar_code : &BarcodeBarArray) -> Option<BarcodeBarArray>{
+fn check_partial_bar_code(partial_bar_codes : &mut Vec<BarcodeBars>, full_codes : &[BarcodeBars],bar_code : &BarcodeBars) -> Option<BarcodeBars>{
     let prev = partial_bar_codes.last().unwrap();
-    let len = (bar_code.0[3] - bar_code.0[2]) / 2;
-    let st =  bar_code.0[0] - prev.0[0];
-    if len >= st{
-        if prev.0[2] > bar_code.0[2]{
-            if prev.0[2] > bar_code.0[2] + len && prev.0[2] < bar_code.0[3] + len {
-                let new = (bar_code.0.clone(),bar_code.1.clone(),prev.1.clone());
+    let len = (bar_code.end - bar_code.start) / 2;
+    let st =  bar_code.row - prev.row;
+    if len >= st && prev.symbology == bar_code.symbology{
+        if prev.start > bar_code.start{
+            if prev.start > bar_code.start + len && prev.start < bar_code.end + len {
+                let new = BarcodeBars {right: prev.left, ..*bar_code};
                 return Some(new);
             }
         } else {
-            if bar_code.0[2] > prev.0[2] + len && bar_code.0[2] < prev.0[3] + len {
-                let new = (bar_code.0.clone(),prev.1.clone(),bar_code.1.clone());
+            if bar_code.start > prev.start + len && bar_code.start < prev.end + len {
+                let new = BarcodeBars {left: prev.left, right: bar_code.left, ..*bar_code};
                 return Some(new);
             }
         }
     } else {
         partial_bar_codes.truncate(0);
     }
-    if  full_codes.len() > 0 {
+    if  !full_codes.is_empty() {
         let prev = full_codes.last().unwrap();
-        if prev.0[0] >= st{
+        // Positions can be compared only with the codes found from the lines of the same angle.
+        if prev.row >= st && prev.symbology == bar_code.symbology && prev.deg == bar_code.deg{
             // Partial barcode starts before the full code.
-            if prev.0[2] > bar_code.0[2]{
-                if prev.0[2] < bar_code.0[2] + len && prev.0[2] < bar_code.0[3] + len {
-                    let new = (bar_code.0.clone(),bar_code.1.clone(),prev.1.clone());
+            if prev.start > bar_code.start{
+                if prev.start < bar_code.start + len && prev.start < bar_code.end + len {
+                    let new = BarcodeBars {right: prev.left, addon: prev.addon, ..*bar_code};
                     return Some(new);
                 }
             } else {
-                let middle = prev.0[2]/2 + prev.0[3]/2;
+                let middle = prev.start/2 + prev.end/2;
                 // Partial barcode starts after the full code and before the middle part of full code.
-                if middle > bar_code.0[2]{
-                    return if middle < bar_code.0[2] + len {
-                        let new = (bar_code.0.clone(), prev.1.clone(), bar_code.1.clone());
+                if middle > bar_code.start{
+                    return if middle < bar_code.start + len {
+                        let new = BarcodeBars {left: prev.left, right: bar_code.left, addon: prev.addon, ..*bar_code};
                         Some(new)
                     } else {
-                        let new = (bar_code.0.clone(), bar_code.1.clone(), prev.2.clone());
+                        let new = BarcodeBars {right: prev.right, addon: prev.addon, ..*bar_code};
                         Some(new)
                     }
                 } else {
-                    if middle + len > bar_code.0[2] {
-                        let new = (bar_code.0.clone(), prev.1.clone(), bar_code.1.clone());
+                    if middle + len > bar_code.start {
+                        let new = BarcodeBars {left: prev.left, right: bar_code.left, addon: prev.addon, ..*bar_code};
                         return Some(new);
                     }
                 }
@@ -168,18 +559,17 @@ fn check_partial_bar_code(partial_bar_codes : &mut Vec<BarcodeBarArray>, full_co
 }
 
 
-fn calculate_row_step(y: u32) -> u32{
-    let lnst = (y as f64).log10();
-    let step = (lnst * 6.0) as u32;
-    return step;
-}
-
-fn are_barcodes_same(a : &BarcodeBarArray, b : &BarcodeBarArray) -> bool{
+fn are_barcodes_same(a : &BarcodeBars, b : &BarcodeBars) -> bool{
+    if a.symbology != b.symbology {
+        return false;
+    }
+    // UPC-E has only one section of digits.
+    let (sa, sb) = if a.symbology == Symbology::UpcE {(&a.left, &b.left)} else {(&a.right, &b.right)};
     let mut i = 0;
-    while i < a.2.len() {
+    while i < sa.len() {
         let mut j = 0;
         while j < 4 {
-            if a.2[i][j] != b.2[i][j] {
+            if sa[i][j] != sb[i][j] {
                 return false;
             }
             j += 1;
@@ -189,89 +579,139 @@ fn are_barcodes_same(a : &BarcodeBarArray, b : &BarcodeBarArray) -> bool{
     true
 }
 
-fn find_bar_code(color_line: &ColorLine, avg_cross : &(bool,Vec<usize>), big_image: bool) -> BarcodeBarArray{
+/**
+Finds EAN-13, EAN-8 or UPC-E barcode from the line.
+Returns the full barcode if there is one, otherwise a half of the barcode, or None if nothing is found.
+**/
+fn find_bar_code(color_line: &ColorLine, avg_cross : &(bool,Vec<usize>), diffs: &[usize], big_image: bool, tolerance: f32) -> Option<BarcodeBars>{
 
-    let mut bar_code_widths : BarcodeBarArray = ([0;5],[[0;4];6],[[0;4];6]);
-    let mut partial_barcodes : Vec<BarcodeBarArray> = Vec::new();
+    let mut partial_barcodes : Vec<BarcodeBars> = Vec::new();
     let c_len = avg_cross.1.len();
-    if c_len >= 32{
-        let mut diffs : Vec<usize> = Vec::with_capacity(c_len-1);
-        let mut f = 0;
-        let mut t = 1;
-        while t < c_len{
-            let diff = avg_cross.1[t] - avg_cross.1[f];
-            diffs.push(diff);
-            t += 1;
-            f += 1;
-        }
+    let min_len = half_symbol_len(EAN8_HALF_DIGITS);
+    if c_len >= min_len{
         let mut light = avg_cross.0;
-        for t in 2..(c_len-31) {
+        for t in 2..(c_len-min_len+1) {
             light = !light;
             let f = t-2;
-            let rangechange = (diffs[f] as f32 * 0.12) as usize + (if big_image {4} else {2});
+            let rangechange = (diffs[f] as f32 * tolerance) as usize + (if big_image {4} else {2});
             let range = (max(rangechange+1,diffs[f])-rangechange,diffs[f]+rangechange);
-            let mut rangem = range.clone();
+            let mut rangem = range;
             if rangechange > 2 {
                 rangem.1 += 2;
             }
-            if diffs[t] >= range.0 && diffs[t] <=  range.1{
-                if diffs[f+1] >= rangem.0 && diffs[f+1] <=  rangem.1{
-                    let m_e = has_bar_code_middle_and_end(&diffs,t,&range, &rangem);
-                    if m_e.0 {
-                        if m_e.1 {
-                            let ulen = find_unit_len(avg_cross.1[f], avg_cross.1[t + 1], &color_line.values, light);
-                            bar_code_widths.1 = parse_barcode_section(t + 1, &diffs, ulen, color_line, avg_cross);
-                            if bar_code_widths.1[5][0] == 0 {
-                                continue;
-                            }
-                            bar_code_widths.2 = parse_barcode_section(t + 30, &diffs, ulen, color_line, avg_cross);
-                            if bar_code_widths.2[5][0] == 0 {
-                                continue;
-                            }
-                            bar_code_widths.0 = [color_line.pos as usize, color_line.deg as usize, avg_cross.1[f], avg_cross.1[t + 55],2];
-                            return bar_code_widths;
-                        } else {
-                            bar_code_widths = ([0;5],[[0;4];6],[[0;4];6]);
-                            let mut pos = t;
-                            if m_e.2 {
-                                pos = t+1;
-                            }
-                            let ulen = find_unit_len(avg_cross.1[f], avg_cross.1[t + 1], &color_line.values, light);
-                            let part = parse_barcode_section(pos + 1, &diffs, ulen, color_line, avg_cross);
-                            if part[5][0] == 0 {
-                                continue;
-                            }
-                            bar_code_widths.1 = part;
-                            bar_code_widths.0 = [color_line.pos as usize, color_line.deg as usize, avg_cross.1[pos], avg_cross.1[t + 29],1];
-                            partial_barcodes.push(bar_code_widths);
-                        }
+            if diffs[t] < range.0 || diffs[t] > range.1 || diffs[f+1] < rangem.0 || diffs[f+1] > rangem.1 {
+                continue;
+            }
+            let mut partial = None;
+            for symbology in [Symbology::Ean13, Symbology::Ean8].iter() {
+                let half = ean_half_digits(*symbology);
+                if t + half_symbol_len(half) > c_len + 1 {
+                    continue;
+                }
+                let m_e = has_bar_code_middle_and_end(diffs,t,half,&range, &rangem);
+                if !m_e.0 {
+                    debug!("{} candidate at {} on line {} deg {} rejected: no middle guard", symbology, avg_cross.1[f], color_line.pos, color_line.deg);
+                    continue;
+                }
+                if m_e.1 {
+                    let mid = t + half * 4 + 1;
+                    // EAN-8 has less digits to check, random bars pass as it without the quiet zones.
+                    if *symbology == Symbology::Ean8 && !has_quiet_zones(color_line, avg_cross, f, mid + half * 4 + 8) {
+                        debug!("EAN-8 candidate at {} on line {} deg {} rejected: no quiet zone", avg_cross.1[f], color_line.pos, color_line.deg);
+                        continue;
+                    }
+                    let ulen = find_unit_len(avg_cross.1[f], avg_cross.1[t + 1], &color_line.values, light);
+                    let left = parse_barcode_section(t + 1, half, diffs, ulen, color_line, avg_cross);
+                    if left[half - 1][0] == 0 {
+                        debug!("{} candidate at {} on line {} deg {} rejected: bad widths on the left side", symbology, avg_cross.1[f], color_line.pos, color_line.deg);
+                        continue;
+                    }
+                    let right = parse_barcode_section(mid + 5, half, diffs, ulen, color_line, avg_cross);
+                    if right[half - 1][0] == 0 {
+                        debug!("{} candidate at {} on line {} deg {} rejected: bad widths on the right side", symbology, avg_cross.1[f], color_line.pos, color_line.deg);
+                        continue;
                     }
+                    let mut bar_code = BarcodeBars::new(color_line, *symbology, avg_cross.1[f], avg_cross.1[mid + half * 4 + 6], true);
+                    bar_code.left = left;
+                    bar_code.right = right;
+                    if *symbology == Symbology::Ean13 {
+                        bar_code.addon = find_addon(mid + half * 4 + 8, diffs, ulen, color_line, avg_cross);
+                    }
+                    return Some(bar_code);
+                } else if partial.is_none() {
+                    partial = Some((*symbology, m_e.2));
+                }
+            }
+            if has_upce_end(diffs, t, &range, &rangem) {
+                let ulen = find_unit_len(avg_cross.1[f], avg_cross.1[t + 1], &color_line.values, light);
+                let part = parse_barcode_section(t + 1, EAN13_HALF_DIGITS, diffs, ulen, color_line, avg_cross);
+                if !has_quiet_zones(color_line, avg_cross, f, t + 31) {
+                    debug!("UPC-E candidate at {} on line {} deg {} rejected: no quiet zone", avg_cross.1[f], color_line.pos, color_line.deg);
+                } else if part[EAN13_HALF_DIGITS - 1][0] == 0 {
+                    debug!("UPC-E candidate at {} on line {} deg {} rejected: bad widths", avg_cross.1[f], color_line.pos, color_line.deg);
+                } else {
+                    let mut bar_code = BarcodeBars::new(color_line, Symbology::UpcE, avg_cross.1[f], avg_cross.1[t + 31], true);
+                    bar_code.left = part;
+                    bar_code.addon = find_addon(t + 31, diffs, ulen, color_line, avg_cross);
+                    return Some(bar_code);
+                }
+            }
+            // Only half of the barcode is visible, full matches of both symbologies are checked before.
+            if let Some((symbology, shifted)) = partial {
+                let half = ean_half_digits(symbology);
+                let mut pos = t;
+                if shifted {
+                    pos = t+1;
+                }
+                let ulen = find_unit_len(avg_cross.1[f], avg_cross.1[t + 1], &color_line.values, light);
+                let part = parse_barcode_section(pos + 1, half, diffs, ulen, color_line, avg_cross);
+                if part[half - 1][0] == 0 {
+                    debug!("{} half at {} on line {} deg {} rejected: bad widths", symbology, avg_cross.1[pos], color_line.pos, color_line.deg);
+                    continue;
                 }
+                let mut bar_code = BarcodeBars::new(color_line, symbology, avg_cross.1[pos], avg_cross.1[t + half * 4 + 5], false);
+                bar_code.left = part;
+                partial_barcodes.push(bar_code);
             }
         }
     }
     if partial_barcodes.len() > 1 {
-        match combine_row_barcode_parts(partial_barcodes) {
-            Some(code) => {
-                bar_code_widths = code;
-            }
-            _ => {}
+        if let Some(code) = combine_row_barcode_parts(&partial_barcodes) {
+            return Some(code);
         }
     }
-    return bar_code_widths;
+    return partial_barcodes.last().cloned();
+}
+
+///Number of digits on each side of the middle guard.
+fn ean_half_digits(symbology: Symbology) -> usize {
+    match symbology {
+        Symbology::Ean8 => EAN8_HALF_DIGITS,
+        _ => EAN13_HALF_DIGITS,
+    }
+}
+
+///Number of crossings needed for the start guard, one half of the digits and the middle guard.
+fn half_symbol_len(half_digits: usize) -> usize {
+    half_digits * 4 + 8
 }
 
-fn combine_row_barcode_parts(partial_barcodes: Vec<BarcodeBarArray>) -> Option<BarcodeBarArray>{
+fn combine_row_barcode_parts(partial_barcodes: &[BarcodeBars]) -> Option<BarcodeBars>{
     let mut sc_ix= 1;
     for (pos, partial1) in partial_barcodes.iter().enumerate() {
         if sc_ix <= pos {
             sc_ix = pos + 1;
         }
-        for ix2 in sc_ix..partial_barcodes.len() {
-            let partial2 = partial_barcodes[ix2];
-            if partial1.0[3] == partial2.0[2] {
-                let meta = [partial1.0[0], partial1.0[1], partial1.0[2], partial2.0[3], 2];
-                return Some((meta, partial1.1.clone(),partial2.1.clone()));
+        for partial2 in &partial_barcodes[sc_ix..] {
+            if partial1.end == partial2.start && partial1.symbology == partial2.symbology {
+                let combined = BarcodeBars {
+                    end: partial2.end,
+                    end_point: partial2.end_point,
+                    full: true,
+                    right: partial2.left,
+                    ..*partial1
+                };
+                return Some(combined);
             }
         }
     }
@@ -280,17 +720,18 @@ fn combine_row_barcode_parts(partial_barcodes: Vec<BarcodeBarArray>) -> Option<B
 
 
 fn has_bar_code_middle_and_end (
-    diffs: &Vec<usize>,
+    diffs: &[usize],
     t: usize,
+    half_digits: usize,
     range: &(usize,usize),
     rangem:  &(usize,usize)) -> (bool,bool,bool
 ) {
-    let middle = &diffs[t+25..t+30];
+    let mid = t + half_digits * 4 + 1;
+    let middle = &diffs[mid..mid+5];
     let mut has_middle = true;
     let first = middle.first().unwrap();
-    for m in 1..middle.len()-1{
-        let elem = middle[m];
-        if elem < range.0 || elem > range.1{
+    for elem in &middle[1..middle.len()-1]{
+        if *elem < range.0 || *elem > range.1{
             has_middle = false;
             break;
         }
@@ -301,10 +742,11 @@ fn has_bar_code_middle_and_end (
         return (has_middle, false, true);
     }
 
-    if !has_middle || diffs.len() < t+57{
+    let end_start = mid + half_digits * 4 + 5;
+    if !has_middle || diffs.len() < end_start+3{
         return (has_middle,false,false);
     }
-    let end = &diffs[t+54..t+57];
+    let end = &diffs[end_start..end_start+3];
     let mut has_end = true;
     for m in end{
         if *m < range.0 || *m > range.1{
@@ -314,8 +756,97 @@ fn has_bar_code_middle_and_end (
     return (has_middle,has_end, false);
 }
 
-fn parse_barcode_section(mut r:usize, diffs: &Vec<usize>, ulen:f32, color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> [[u8; 4]; 6] {
-    let section_end = r+24;
+/**
+True if the light areas before the start guard and after the end guard are wide enough.
+The module width is taken from the start guard.
+Parameters:
+first - index of the first bar of the start guard in the widths.
+after - index of the light element after the last bar of the end guard.
+**/
+fn has_quiet_zones(color_line: &ColorLine, avg_cross : &(bool,Vec<usize>), first: usize, after: usize) -> bool {
+    let cross = &avg_cross.1;
+    let module = (cross[first + 3] - cross[first]) as f32 / 3.0;
+    let before = cross[first] - if first == 0 {0} else {cross[first - 1]};
+    let after = cross.get(after + 1).unwrap_or(&color_line.values.len()) - cross[after];
+    before as f32 >= module * EAN_QUIET_ZONE_MODULES && after as f32 >= module * EAN_QUIET_ZONE_MODULES
+}
+
+/**
+UPC-E has 6 digits followed by the special end guard (6 narrow elements) and the quiet zone.
+Quiet zone separates it from the EAN-13 middle guard which is followed by more digits.
+**/
+fn has_upce_end(
+    diffs: &[usize],
+    t: usize,
+    range: &(usize,usize),
+    rangem: &(usize,usize)) -> bool
+{
+    let end_start = t + EAN13_HALF_DIGITS * 4 + 1;
+    if diffs.len() < end_start + 6 {
+        return false;
+    }
+    let end = &diffs[end_start..end_start + 6];
+    for elem in &end[1..5] {
+        if *elem < range.0 || *elem > range.1 {
+            return false;
+        }
+    }
+    if end[0] < rangem.0 || end[0] > rangem.1 || end[5] < rangem.0 || end[5] > rangem.1 {
+        return false;
+    }
+    diffs.len() == end_start + 6 || diffs[end_start + 6] > range.1 * 4
+}
+
+/**
+Looks for EAN-2 or EAN-5 add-on after the end guard of the main barcode.
+r - index of the quiet zone between the main barcode and the add-on.
+Add-on starts with 1011 pattern and has 01 separators between the digits.
+Returns zeros if add-on is not found or if it has other than 2 or 5 digits.
+**/
+fn find_addon(r: usize, diffs: &[usize], ulen: f32, color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> [[u8; 4]; 5] {
+    let mut ret_codes = [[0;4];5];
+    if diffs.len() < r + 14 {
+        return ret_codes;
+    }
+    let gap = diffs[r] as f32 / ulen;
+    if !(4.0..=16.0).contains(&gap) {
+        return ret_codes;
+    }
+    if !is_module_width(diffs[r + 1], ulen, 1.0) || !is_module_width(diffs[r + 2], ulen, 1.0) || !is_module_width(diffs[r + 3], ulen, 2.0) {
+        return ret_codes;
+    }
+    let mut d = r + 4;
+    let mut ix = 0;
+    while ix < 5 {
+        if ix > 0 {
+            if diffs.len() < d + 6 || !is_module_width(diffs[d], ulen, 1.0) || !is_module_width(diffs[d + 1], ulen, 1.0) {
+                break;
+            }
+            d += 2;
+        }
+        let s_ix = avg_cross.1[d];
+        let e_ix = avg_cross.1[d + 4];
+        let avg_ix = s_ix/color_line.slice_size;
+        let codes = parse_ean_code(&diffs[d..d + 4], ulen, &color_line.values[s_ix..e_ix], color_line.avg_loc[avg_ix]);
+        if codes.len() != 4 {
+            return [[0;4];5];
+        }
+        ret_codes[ix].copy_from_slice(&codes);
+        d += 4;
+        ix += 1;
+    }
+    if ix != 2 && ix != 5 {
+        return [[0;4];5];
+    }
+    ret_codes
+}
+
+fn is_module_width(width: usize, ulen: f32, modules: f32) -> bool {
+    (width as f32 - modules * ulen).abs() <= ulen * 0.6
+}
+
+fn parse_barcode_section(mut r:usize, digits: usize, diffs: &[usize], ulen:f32, color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> [[u8; 4]; 6] {
+    let section_end = r+digits*4;
     let mut ix= 0;
     let mut ret_codes = [[0;4];6];
     while r < section_end {
@@ -324,7 +855,7 @@ fn parse_barcode_section(mut r:usize, diffs: &Vec<usize>, ulen:f32, color_line:
         let e_ix = avg_cross.1[n+1];
         let avg_ix = s_ix/color_line.slice_size;
         let codes = parse_ean_code(&diffs[r..n], ulen, &color_line.values[s_ix..e_ix], color_line.avg_loc[avg_ix]);
-        if codes.len() == 0 {
+        if codes.is_empty() {
             break;
         }
         let mut i= 0;
@@ -339,10 +870,10 @@ fn parse_barcode_section(mut r:usize, diffs: &Vec<usize>, ulen:f32, color_line:
 }
 
 fn parse_ean_code(lens: &[usize], unit: f32, vals: &[u8], avg_col: u8) -> Vec<u8>{
-    return parse_number_bars(&lens, unit, vals, avg_col, 7, 4);
+    return parse_number_bars(lens, unit, vals, avg_col, 7, 4);
 }
 
-fn parse_number_bars(lens: &[usize], unit: f32, vals: &[u8], avg_col: u8, units: u8, max_len: u8) -> Vec<u8>{
+pub fn parse_number_bars(lens: &[usize], unit: f32, vals: &[u8], avg_col: u8, units: u8, max_len: u8) -> Vec<u8>{
     let len = lens.len();
     let mut divs= Vec::with_capacity(len);
     let mut fracs = vec![(0,-1.0,&[] as &[u8]);len];
@@ -356,12 +887,12 @@ fn parse_number_bars(lens: &[usize], unit: f32, vals: &[u8], avg_col: u8, units:
             parts = 1.0;
         } else {
             if *n > 4 {
-                let edges = check_bar_edge(&cur_vals, avg_col);
+                let edges = check_bar_edge(cur_vals, avg_col);
                 if edges.0 < 0.05 {
-                    parts = parts-0.1;
+                    parts -= 0.1;
                 }
                 if edges.1 < 0.05 {
-                    parts = parts-0.1;
+                    parts -= 0.1;
                 }
             }
         }
@@ -412,12 +943,11 @@ fn parse_number_bars(lens: &[usize], unit: f32, vals: &[u8], avg_col: u8, units:
     }
     if total == units + 1{
         let lastf = fracs.last().unwrap();
-        if divs[lastf.0] > 1 {
-            if lastf.1 < 0.2{
+        if divs[lastf.0] > 1
+            && lastf.1 < 0.2{
                 divs[lastf.0] -= 1;
                 total -= 1;
             }
-        }
     }
     if total > units{
         return Vec::with_capacity(0);
@@ -425,7 +955,7 @@ fn parse_number_bars(lens: &[usize], unit: f32, vals: &[u8], avg_col: u8, units:
     return divs;
 }
 
-fn check_bar_edge(cur_vals : &[u8],avg_col:u8) -> (f32,f32) {
+pub fn check_bar_edge(cur_vals : &[u8],avg_col:u8) -> (f32,f32) {
     let df;
     let dt ;
     let px = cur_vals[0];
@@ -471,14 +1001,14 @@ fn compare_bar_by_color(vals1: &[u8], vals2: &[u8]) -> i32{
     }
     let ct : i32 = extremes.1 as i32 + extremes.0 as i32;
     if sums.1 > sums.0 {
-        return ((sums.1 + sums.0) - len as i32*ct) * -1;
+        return -((sums.1 + sums.0) - len as i32*ct);
     } else {
         return (sums.1 + sums.0) - len as i32*ct;
     }
 }
 
 
-fn find_unit_len(start: usize, end: usize, row: &Vec<u8>,is_inverted:bool) -> f32{
+fn find_unit_len(start: usize, end: usize, row: &[u8],is_inverted:bool) -> f32{
     let nums = &row[start..end];
     let mut max = nums[0];
     let mut min = nums[0];
@@ -496,32 +1026,32 @@ fn find_unit_len(start: usize, end: usize, row: &Vec<u8>,is_inverted:bool) -> f3
     if is_inverted {
         sides = ((max - nums[0]) as f32 / diff,(max - *nums.last().unwrap()) as f32 / diff);
         if start>0 && row[start-1] < max && end-start>6{
-            sides.0 = sides.0 - (max.saturating_sub(row[start-1])) as f32 / diff;
+            sides.0 -= (max.saturating_sub(row[start-1])) as f32 / diff;
         } else {
-            sides.0 = sides.0/2.2;
-            sides.1 = sides.1/2.2;
-            if start>0 {
-                if (row[start-1].saturating_sub(min)) as f32 / diff < 0.06 {
+            sides.0 /= 2.2;
+            sides.1 /= 2.2;
+            if start>0
+                && (row[start-1].saturating_sub(min)) as f32 / diff < 0.06 {
                     sides.0 = 0.0;
                 }
-            }
-            if (row[end+1].saturating_sub(min)) as f32 / diff < 0.06 {
+            if end + 1 < row.len()
+                && (row[end+1].saturating_sub(min)) as f32 / diff < 0.06 {
                 sides.1 = 0.0;
             }
         }
     } else {
         sides = ((nums[0]-min) as f32 / diff,(nums.last().unwrap()-min) as f32 / diff);
         if start>0 && row[start-1] > min && end-start>6{
-            sides.0 = sides.0 - (row[start-1].saturating_sub(min)) as f32 / diff;
+            sides.0 -= (row[start-1].saturating_sub(min)) as f32 / diff;
         } else {
-            sides.0 = sides.0/2.2;
-            sides.1 = sides.1/2.2;
-            if start>0 {
-                if (row[start-1].saturating_sub(min)) as f32 / diff > 0.94 {
+            sides.0 /= 2.2;
+            sides.1 /= 2.2;
+            if start>0
+                && (row[start-1].saturating_sub(min)) as f32 / diff > 0.94 {
                     sides.0 = 0.0;
                 }
-            }
-            if (row[end+1].saturating_sub(min)) as f32 / diff > 0.94 {
+            if end + 1 < row.len()
+                && (row[end+1].saturating_sub(min)) as f32 / diff > 0.94 {
                 sides.1 = 0.0;
             }
         }
@@ -531,3 +1061,228 @@ fn find_unit_len(start: usize, end: usize, row: &Vec<u8>,is_inverted:bool) -> f3
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aztec_reader::COMPACT_MODULES;
+    use crate::barcode_translate::{addon_widths, ean_widths, upce_widths};
+    use crate::codabar_reader::codabar_widths;
+    use crate::code128_reader::{code128_widths, START_B};
+    use crate::code39_reader::code39_widths;
+    use crate::code93_reader::{code93_values, code93_widths};
+    use crate::databar_reader::{DATABAR_MODULES, EXPANDED_MODULES};
+    use crate::datamatrix_reader::ASCII_MODULES;
+    use crate::itf_reader::itf_widths;
+    use crate::pixel_sources::RawImage;
+    use crate::qr_reader::QR_MODULES;
+    use crate::test_images::{module_widths, read_modules, read_widths};
+
+    /// Codes are drawn upside down, along the columns in both directions and rotated.
+    const ANY_DIRECTION: [f32; 5] = [90.0, 180.0, 200.0, 270.0, 35.0];
+
+    ///Linear codes of every reader with their texts, the EAN add-on is not read from the rotated lines.
+    fn linear_codes() -> Vec<(Vec<usize>, &'static str, &'static [f32])> {
+        let mut addon = ean_widths("9780306406157");
+        addon.extend(addon_widths("51299"));
+        vec![
+            (ean_widths("4006381333931"), "EAN-13 4006381333931", &ANY_DIRECTION),
+            (ean_widths("96385074"), "EAN-8 96385074", &ANY_DIRECTION),
+            (upce_widths("01234565"), "UPC-E 01234565", &ANY_DIRECTION),
+            (addon, "EAN-13 9780306406157 51299", &ANY_DIRECTION[..4]),
+            (code128_widths(&[START_B, 35, 79, 68, 69]), "Code 128 Code", &ANY_DIRECTION),
+            (code39_widths("ABC-123"), "Code 39 ABC-123", &ANY_DIRECTION),
+            (code93_widths(&code93_values("TEST93")), "Code 93 TEST93", &ANY_DIRECTION),
+            (itf_widths("15400141288763"), "ITF-14 15400141288763", &ANY_DIRECTION),
+            (codabar_widths("A40156B"), "Codabar 40156", &ANY_DIRECTION),
+            (module_widths(DATABAR_MODULES), "GS1 DataBar 00012345678905", &ANY_DIRECTION),
+            (module_widths(EXPANDED_MODULES), "GS1 DataBar Expanded 0112345678901231", &ANY_DIRECTION),
+        ]
+    }
+
+    ///Symbology and text of the codes, with the add-on if there is one.
+    fn code_texts(codes: &[Barcode]) -> Vec<String> {
+        codes.iter().map(|c| match &c.addon {
+            Some(addon) => format!("{} {} {}", c.symbology, c.text, addon),
+            None => format!("{} {}", c.symbology, c.text),
+        }).collect()
+    }
+
+    #[test]
+    fn reads_linear_codes_in_any_direction() {
+        let options = DecoderOptions::default();
+        for (widths, text, angles) in linear_codes() {
+            for deg in angles {
+                assert_eq!(code_texts(&read_widths(&widths, *deg, &options)), vec![text], "at {} degrees", deg);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_the_direction_of_the_code() {
+        let options = DecoderOptions::default();
+        let directions = [
+            (0.0, ScanPass::Rows, 0, false),
+            (180.0, ScanPass::Rows, 180, true),
+            (90.0, ScanPass::Columns, 90, false),
+            (270.0, ScanPass::Columns, 270, true),
+        ];
+        for (widths, text, _) in linear_codes() {
+            for (deg, pass, line_deg, reversed) in directions.iter() {
+                let codes = read_widths(&widths, *deg, &options);
+                let found = codes.iter().map(|c| (c.pass.unwrap(), c.deg.unwrap(), c.reversed)).collect::<Vec<_>>();
+                assert_eq!(found, vec![(*pass, *line_deg, *reversed)], "{} at {} degrees", text, deg);
+            }
+            // Rotated lines are at the nearest angle, on the same side of the half circle.
+            for (deg, reversed) in [(35.0, false), (200.0, true)].iter() {
+                let codes = read_widths(&widths, *deg, &options);
+                let found : Vec<bool> = codes.iter().map(|c| c.reversed == (c.deg.unwrap() >= 180) && c.reversed == *reversed).collect();
+                assert_eq!(found, vec![true], "{} at {} degrees", text, deg);
+            }
+        }
+    }
+
+    #[test]
+    fn reads_2d_symbols_in_any_rotation() {
+        let options = DecoderOptions::default().symbologies(&Symbology::ALL);
+        let symbols : [(&[&str], &str); 3] = [
+            (&QR_MODULES, "QR Code Hello, QR 123"),
+            (&ASCII_MODULES, "Data Matrix Hello World 123"),
+            (&COMPACT_MODULES, "Aztec HELLOworld123"),
+        ];
+        for (rows, text) in symbols.iter() {
+            for deg in ANY_DIRECTION.iter() {
+                assert_eq!(read_modules(rows, *deg, &options), vec![*text], "at {} degrees", deg);
+            }
+        }
+    }
+
+    fn code39_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::Code39])
+    }
+
+    ///Pass and angle of the line the code was read from, the first found code.
+    fn read_line_of(deg: f32, options: &DecoderOptions) -> Option<(ScanPass, u16)> {
+        let codes = read_widths(&code39_widths("ABC-123"), deg, options);
+        codes.first().map(|c| (c.pass.unwrap(), c.deg.unwrap()))
+    }
+
+    #[test]
+    fn reports_the_scan_line() {
+        let options = code39_options();
+        assert_eq!(read_line_of(0.0, &options), Some((ScanPass::Rows, 0)));
+        assert_eq!(read_line_of(180.0, &options), Some((ScanPass::Rows, 180)));
+        assert_eq!(read_line_of(90.0, &options), Some((ScanPass::Columns, 90)));
+        assert_eq!(read_line_of(270.0, &options), Some((ScanPass::Columns, 270)));
+        assert_eq!(read_line_of(45.0, &options), Some((ScanPass::Rotated, 45)));
+        assert_eq!(read_line_of(225.0, &options), Some((ScanPass::Rotated, 225)));
+    }
+
+    #[test]
+    fn scans_only_the_given_angles() {
+        let rows = code39_options().scan_angles(&[0]);
+        assert_eq!(read_line_of(180.0, &rows), Some((ScanPass::Rows, 180)));
+        assert_eq!(read_line_of(90.0, &rows), None);
+        assert_eq!(read_line_of(45.0, &rows), None);
+        let columns = code39_options().scan_angles(&[90]);
+        assert_eq!(read_line_of(0.0, &columns), None);
+        assert_eq!(read_line_of(270.0, &columns), Some((ScanPass::Columns, 270)));
+        // Angles over 180 are the same lines read in the other direction.
+        let rotated = code39_options().scan_angles(&[210]);
+        assert_eq!(read_line_of(30.0, &rotated), Some((ScanPass::Rotated, 30)));
+    }
+
+    #[test]
+    fn by_rows_reads_only_the_rows() {
+        use crate::test_images::draw_widths;
+        for (deg, found) in [(0.0, true), (180.0, true), (90.0, false), (30.0, false)] {
+            let (pixels, w, h) = draw_widths(&code39_widths("ROWS"), deg);
+            let img = RawImage::new(&pixels, w, h, 1, w as usize).unwrap();
+            let codes = process_image_by_rows(&img, (w, h), 0).unwrap();
+            assert_eq!(codes.iter().any(|c| c.text == "ROWS"), found, "at {} degrees", deg);
+            assert!(codes.iter().all(|c| c.pass == Some(ScanPass::Rows)));
+        }
+    }
+
+    #[test]
+    fn dimensions_bigger_than_the_image_are_rejected() {
+        let data = vec![255; 20 * 20];
+        let img = RawImage::new(&data, 20, 20, 1, 20).unwrap();
+        let options = DecoderOptions::default();
+        assert!(matches!(process_image_with_options(&img, (21, 20), &options), Err(Error::InvalidBuffer(_))));
+        assert!(matches!(process_image_with_options(&img, (20, 40), &options), Err(Error::InvalidBuffer(_))));
+        assert!(process_image_with_options(&img, (20, 20), &options).is_ok());
+        assert!(process_image_with_options(&img, (15, 12), &options).is_ok());
+    }
+
+    #[test]
+    fn noise_images_are_read_without_panics() {
+        // Xorshift, the images are the same on every run. Some of them have DataBar Expanded finders with missing characters.
+        let mut seed : u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut random = |n: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % n
+        };
+        let options = DecoderOptions::default().symbologies(&Symbology::ALL);
+        for i in 0..300 {
+            let (w, h) = (20 + random(160) as u32, 20 + random(160) as u32);
+            let mut data = vec![0; (w * h) as usize];
+            match i % 3 {
+                // Pixel noise.
+                0 => data.iter_mut().for_each(|v| *v = random(256) as u8),
+                // Random bars across the image.
+                1 => {
+                    let bars : Vec<u8> = (0..w).map(|_| if random(3) == 0 {0} else {255}).collect();
+                    data.iter_mut().enumerate().for_each(|(ix, v)| *v = bars[ix % w as usize]);
+                },
+                // Random modules like in the 2D symbols.
+                _ => {
+                    let module = 1 + random(5) as u32;
+                    let modules : Vec<u8> = (0..(w / module + 1) * (h / module + 1)).map(|_| if random(2) == 0 {0} else {255}).collect();
+                    data.iter_mut().enumerate().for_each(|(ix, v)| {
+                        let (x, y) = (ix as u32 % w / module, ix as u32 / w / module);
+                        *v = modules[(y * (w / module + 1) + x) as usize];
+                    });
+                },
+            }
+            let img = RawImage::new(&data, w, h, 1, w as usize).unwrap();
+            assert!(process_image_with_options(&img, (w, h), &options).is_ok());
+        }
+    }
+
+    #[cfg(all(feature = "parallel", feature = "image"))]
+    #[test]
+    fn parallel_results_are_sequential_results() {
+        use std::cell::Cell;
+        use image::GenericImageView;
+
+        ///Source that counts the pixel reads, it is not Sync.
+        struct CountingSource<'a> {
+            img: &'a image::DynamicImage,
+            reads: Cell<usize>,
+        }
+
+        impl PixelValue for CountingSource<'_> {
+            fn get_pixel_value(&self, x: u32, y: u32, channel: usize) -> u8 {
+                self.reads.set(self.reads.get() + 1);
+                self.img.get_pixel_value(x, y, channel)
+            }
+        }
+
+        let cases = [
+            ("test/img/veenus_crop.jpg", DecoderOptions::default()),
+            ("test/img/veenus_crop_invert.jpg", DecoderOptions::default().scan_angles(&[0, 90]).try_harder(true)),
+        ];
+        for (path, options) in cases.iter() {
+            let img = image::open(path).unwrap();
+            let sequential = process_image_with_options(&img, img.dimensions(), options).unwrap();
+            let source = CountingSource { img: &img, reads: Cell::new(0) };
+            let parallel = process_image_parallel(&source, img.dimensions(), options).unwrap();
+            assert!(!sequential.is_empty(), "{}", path);
+            assert_eq!(parallel, sequential, "{}", path);
+            assert!(source.reads.get() > 0);
+        }
+    }
+}
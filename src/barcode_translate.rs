@@ -1,4 +1,6 @@
-use crate::BarcodeBarArray;
+use log::debug;
+use crate::barcode_detector::BarcodeBars;
+use crate::symbology::{Barcode, Symbology};
 
 const BARCODE_DICT_POS : [[usize;5];4] = [[0,4,7,9,10],[10,13,15,16,16],[16,18,19,19,19],[19,20,20,20,20]];
 
@@ -25,60 +27,270 @@ const BARCODE_DICT : [(u8,[u8;4],bool);20] = [
         (6, [4, 1, 1, 1], false),
 ];
 
+/// Parity of the UPC-E digits for number system 0, indexed by check digit. Bit is set for even parity.
+/// Number system 1 uses the inverted patterns.
+const UPCE_PARITY : [u8;10] = [0x38,0x34,0x32,0x31,0x2c,0x26,0x23,0x2a,0x29,0x25];
+
+/// Parity of the EAN-5 add-on digits, indexed by checksum. Bit is set for even parity.
+const EAN5_PARITY : [u8;10] = [0x18,0x14,0x12,0x11,0x0c,0x06,0x03,0x0a,0x09,0x05];
+
 const EAN_PARITY : [(usize,usize);29] = [(1,10),(2,3),(0,0),(4,5),(0,1),(6,7),(0,2),(7,8),(0,3),
     (0,10),(11,18),(12,13),(0,4),(14,15),(0,7),(16,17),(0,8),(0,10),(19,24),(20,21),(0,5),(22,23),(0,9),(0,10),(25,28),(26,27),(0,6),(0,10),(0,10)];
 
 
 /**
-Translates EAN-13 (and UPC-A) barcode from BarcodeBarArray format to array of u8.
+Translates EAN-13 (and UPC-A), EAN-8 or UPC-E barcode from the bar widths to Barcode.
 Does checksum validation, returns None if barcode is invalid;
+EAN-13 and EAN-8 that are not valid are also tried in reverse, as if they were read upside down.
+Scan line, position, module widths and confidence are taken from the bars, the angle of the reversed code has 180 added.
+**/
+pub fn translate_bar_code(bcode: &BarcodeBars) -> Option<Barcode>{
+    let (mut code, bars, deg) = match translate_symbol(bcode) {
+        Some(code) => (code, *bcode, bcode.deg),
+        None => {
+            let reversed = reverse_bar_code(bcode)?;
+            // The code goes in the opposite direction of the line, as if the line was read from the end.
+            (translate_symbol(&reversed)?, reversed, (bcode.deg + 180) % 360)
+        }
+    };
+    code.set_scan_line(bars.row as u32, deg);
+    code.set_span(bars.start_point, bars.end_point);
+    code.set_line_count(bars.lines);
+    code.module_widths = bars.module_widths();
+    Some(code)
+}
+
+fn translate_symbol(bcode: &BarcodeBars) -> Option<Barcode>{
+    match bcode.symbology {
+        Symbology::Ean13 => translate_ean13(bcode),
+        Symbology::Ean8 => translate_ean8(bcode),
+        Symbology::UpcE => translate_upce(bcode),
+        _ => None,
+    }
+}
+
+/**
+Barcode read from the end has the halves swapped and the order of the digits and of their bar widths reversed.
+Only the barcodes read from one line are reversed, the halves from different lines are combined in the forward order.
+Add-on of the upside down barcode is before it on the line, so it is not kept.
+Start and end points are swapped to the start and the end of the barcode, indexes on the line are kept.
+UPC-E has a different end guard and it is not found in reverse.
 **/
-pub fn translate_bar_code(bcode: &BarcodeBarArray) -> Option<[u8;13]>{
+fn reverse_bar_code(bcode: &BarcodeBars) -> Option<BarcodeBars>{
+    if !bcode.full {
+        return None;
+    }
+    let half = match bcode.symbology {
+        Symbology::Ean13 => 6,
+        Symbology::Ean8 => 4,
+        _ => return None,
+    };
+    let reverse = |section: &[[u8;4];6]| {
+        let mut reversed = [[0_u8;4];6];
+        for (i, widths) in reversed.iter_mut().take(half).enumerate() {
+            *widths = section[half - 1 - i];
+            widths.reverse();
+        }
+        reversed
+    };
+    Some(BarcodeBars {
+        start_point: bcode.end_point,
+        end_point: bcode.start_point,
+        left: reverse(&bcode.right),
+        right: reverse(&bcode.left),
+        addon: [[0;4];5],
+        ..*bcode
+    })
+}
 
-    let mut barcode = [0 as u8;13];
+fn translate_ean13(bcode: &BarcodeBars) -> Option<Barcode>{
 
+    let mut barcode = [0_u8;13];
     let mut even_odd : [bool;13] = [false;13];
-    let mut n = 1;
-    let mut valid = true;
 
-    for c in bcode.1.iter(){
-        let num = find_number_from_bars(&c);
-        barcode[n] = num.0;
-        even_odd[n] = num.1;
-        if barcode[n] > 9 {
-            valid = false;
-            break;
-        }
-        n += 1;
+    if !read_digits(&bcode.left, &mut barcode[1..7], &mut even_odd[1..7], false) {
+        return rejected(bcode, "bad widths on the left side");
+    }
+    if !read_digits(&bcode.right, &mut barcode[7..13], &mut even_odd[7..13], true) {
+        return rejected(bcode, "bad widths or parity on the right side");
+    }
+    let first = find_first_number(&even_odd[2..7]);
+    if first > 9 {
+        return rejected(bcode, "parity of the left side does not encode the first digit");
+    }
+    let check = calc_checksum(first,&barcode[1..12]);
+    if check == barcode[12]{
+        barcode[0] = first as u8;
+        let mut code = Barcode::new(Symbology::Ean13, digits_to_text(&barcode));
+        code.addon = translate_addon(&bcode.addon);
+        return Some(code);
+    }
+    rejected(bcode, "checksum mismatch")
+}
+
+/**
+EAN-8 has 4 digits on both sides of the middle guard.
+Left side uses only odd parity (L) codes, so there is no encoded first digit.
+**/
+fn translate_ean8(bcode: &BarcodeBars) -> Option<Barcode>{
+
+    let mut barcode = [0_u8;8];
+    let mut even_odd : [bool;8] = [false;8];
+
+    if !read_digits(&bcode.left[0..4], &mut barcode[0..4], &mut even_odd[0..4], true) {
+        return rejected(bcode, "bad widths or parity on the left side");
+    }
+    if !read_digits(&bcode.right[0..4], &mut barcode[4..8], &mut even_odd[4..8], true) {
+        return rejected(bcode, "bad widths or parity on the right side");
     }
-    if !valid {
+    let check = calc_checksum(0,&barcode[0..7]);
+    if check == barcode[7]{
+        return Some(Barcode::new(Symbology::Ean8, digits_to_text(&barcode)));
+    }
+    rejected(bcode, "checksum mismatch")
+}
+
+/**
+UPC-E has 6 digits without a middle guard.
+Number system (0 or 1) and check digit are encoded in the parity of the digits.
+Check digit is validated against the code expanded to UPC-A.
+**/
+fn translate_upce(bcode: &BarcodeBars) -> Option<Barcode>{
+
+    let mut barcode = [0_u8;8];
+    let mut even_odd : [bool;8] = [false;8];
+
+    if !read_digits(&bcode.left, &mut barcode[1..7], &mut even_odd[1..7], false) {
+        return rejected(bcode, "bad widths");
+    }
+    let (number_system, check) = match find_upce_number_system_and_check(&even_odd[1..7]) {
+        Some(parity) => parity,
+        None => return rejected(bcode, "parity does not encode the number system and the check digit"),
+    };
+    barcode[0] = number_system;
+    barcode[7] = check;
+    let expanded = expand_upce(&barcode);
+    if calc_checksum(expanded[0] as usize, &expanded[1..12]) != expanded[12] {
+        return rejected(bcode, "checksum mismatch");
+    }
+    let mut code = Barcode::new(Symbology::UpcE, digits_to_text(&barcode));
+    code.expanded = Some(digits_to_text(&expanded));
+    code.addon = translate_addon(&bcode.addon);
+    Some(code)
+}
+
+///Logs why the bars were not translated, the result is always None.
+fn rejected(bcode: &BarcodeBars, reason: &str) -> Option<Barcode> {
+    debug!("{} bars on line {} deg {} rejected: {}", bcode.symbology, bcode.row, bcode.deg, reason);
+    None
+}
+
+/**
+Translates EAN-2 or EAN-5 add-on. Number of digits is given by the non-zero widths.
+EAN-2 parity encodes the value modulo 4, EAN-5 parity encodes its checksum.
+**/
+fn translate_addon(bars: &[[u8;4];5]) -> Option<String>{
+    let len = bars.iter().take_while(|c| c[0] != 0).count();
+    if len != 2 && len != 5 {
         return None;
     }
-    for c in bcode.2.iter(){
-        let num = find_number_from_bars(&c);
-        barcode[n] = num.0;
-        even_odd[n] = num.1;
-        if barcode[n] > 9 {
-            valid = false;
-            break;
+    let mut digits = [0_u8;5];
+    let mut even_odd = [false;5];
+    if !read_digits(&bars[0..len], &mut digits[0..len], &mut even_odd[0..len], false) {
+        return None;
+    }
+    let mask = parity_mask(&even_odd[0..len]);
+    let expected = if len == 2 {
+        (digits[0] * 10 + digits[1]) % 4
+    } else {
+        let sum = 3 * (digits[0] + digits[2] + digits[4]) as usize + 9 * (digits[1] + digits[3]) as usize;
+        EAN5_PARITY[sum % 10]
+    };
+    if mask != expected {
+        return None;
+    }
+    Some(digits_to_text(&digits[0..len]))
+}
+
+///Bit mask of digit parities, first digit in the highest bit. Bit is set for even parity.
+fn parity_mask(parity : &[bool]) -> u8{
+    let mut mask = 0;
+    for odd in parity.iter(){
+        mask <<= 1;
+        if !odd {
+            mask |= 1;
         }
-        if even_odd[n] == false {
-            valid = false;
-            break;
+    }
+    mask
+}
+
+fn find_upce_number_system_and_check(parity : &[bool]) -> Option<(u8,u8)>{
+    let mask = parity_mask(parity);
+    for (check, pattern) in UPCE_PARITY.iter().enumerate(){
+        if *pattern == mask {
+            return Some((0, check as u8));
         }
-        n += 1;
-    }
-    if valid {
-        let first = find_first_number(&even_odd[2..7]);
-        let check = calc_checksum(first,&barcode[1..12]);
-        if check as u8 == barcode[12]{
-            barcode[0] = first as u8;
-            return Some(barcode);
+        if *pattern ^ 0x3f == mask {
+            return Some((1, check as u8));
         }
     }
     None
 }
 
+/**
+Expands UPC-E digits (number system, 6 digits, check digit) to EAN-13.
+The last of the 6 digits tells where the suppressed zeros were.
+**/
+fn expand_upce(upce: &[u8;8]) -> [u8;13]{
+    let mut code = [0_u8;13];
+    code[1] = upce[0];
+    code[12] = upce[7];
+    let d = &upce[1..7];
+    match d[5] {
+        0..=2 => {
+            code[2..4].copy_from_slice(&d[0..2]);
+            code[4] = d[5];
+            code[9..12].copy_from_slice(&d[2..5]);
+        },
+        3 => {
+            code[2..5].copy_from_slice(&d[0..3]);
+            code[10..12].copy_from_slice(&d[3..5]);
+        },
+        4 => {
+            code[2..6].copy_from_slice(&d[0..4]);
+            code[11] = d[4];
+        },
+        _ => {
+            code[2..7].copy_from_slice(&d[0..5]);
+            code[11] = d[5];
+        }
+    }
+    code
+}
+
+/**
+Reads digits from bar widths into `digits` and their parity into `even_odd`.
+Returns false if some widths do not match any digit or if `odd_only` is set and a digit has even parity.
+**/
+fn read_digits(bars: &[[u8;4]], digits: &mut [u8], even_odd: &mut [bool], odd_only: bool) -> bool{
+    for (n, c) in bars.iter().enumerate(){
+        let num = find_number_from_bars(c);
+        digits[n] = num.0;
+        even_odd[n] = num.1;
+        if digits[n] > 9 {
+            return false;
+        }
+        if odd_only && !even_odd[n] {
+            return false;
+        }
+    }
+    true
+}
+
+fn digits_to_text(digits: &[u8]) -> String{
+    digits.iter().map(|d| (b'0' + *d) as char).collect()
+}
+
 
 fn find_first_number(parity : &[bool]) -> usize{
     let mut d = 0;
@@ -88,7 +300,11 @@ fn find_first_number(parity : &[bool]) -> usize{
         if elem.0 == 0{
             return elem.1;
         } else {
-            let b = parity[d];
+            // Broken parity pattern does not end at a digit.
+            let b = match parity.get(d) {
+                Some(b) => *b,
+                None => break,
+            };
             if b {
                 cur_ix = elem.0;
             } else {
@@ -130,4 +346,140 @@ fn find_number_from_bars(c : &[u8;4]) -> (u8,bool){
         }
     }
     return (10,false);
-}
\ No newline at end of file
+}
+
+///Widths of the digit, L code for odd parity and G code for even. R code has the L widths starting with a bar.
+#[cfg(test)]
+fn digit_widths(digit: u8, odd: bool) -> Vec<usize> {
+    let entry = BARCODE_DICT.iter().find(|e| e.0 == digit && e.2 == odd).unwrap();
+    entry.1.iter().map(|w| *w as usize).collect()
+}
+
+#[cfg(test)]
+fn digits(text: &str) -> Vec<u8> {
+    text.bytes().map(|d| d - b'0').collect()
+}
+
+///Widths of EAN-13 or EAN-8 from all its digits, the first EAN-13 digit sets the parity of the left side.
+#[cfg(test)]
+pub(crate) fn ean_widths(text: &str) -> Vec<usize> {
+    let digits = digits(text);
+    let (parity, left, right) = if digits.len() == 13 {
+        // Parity of the left digits of EAN-13 for the first digit, bit is set for the odd parity.
+        let parity = [0x3f, 0x34, 0x32, 0x31, 0x2c, 0x26, 0x23, 0x2a, 0x29, 0x25][digits[0] as usize];
+        (parity, &digits[1..7], &digits[7..])
+    } else {
+        (0xff, &digits[..4], &digits[4..])
+    };
+    let mut widths = vec![1, 1, 1];
+    for (i, d) in left.iter().enumerate() {
+        widths.extend(digit_widths(*d, parity >> (left.len() - 1 - i) & 1 == 1));
+    }
+    widths.extend([1, 1, 1, 1, 1]);
+    for d in right {
+        widths.extend(digit_widths(*d, true));
+    }
+    widths.extend([1, 1, 1]);
+    widths
+}
+
+///Widths of UPC-E from its 8 digits, the number system and the check digit set the parity of the 6 digits.
+#[cfg(test)]
+pub(crate) fn upce_widths(text: &str) -> Vec<usize> {
+    let digits = digits(text);
+    let parity = UPCE_PARITY[digits[7] as usize] ^ if digits[0] == 1 {0x3f} else {0};
+    let mut widths = vec![1, 1, 1];
+    for (i, d) in digits[1..7].iter().enumerate() {
+        widths.extend(digit_widths(*d, parity >> (5 - i) & 1 == 0));
+    }
+    widths.extend([1, 1, 1, 1, 1, 1]);
+    widths
+}
+
+///Widths of the EAN-2 or EAN-5 add-on after the code, starting with the gap before it.
+#[cfg(test)]
+pub(crate) fn addon_widths(text: &str) -> Vec<usize> {
+    let digits = digits(text);
+    // Bit is set for the even parity.
+    let parity = if digits.len() == 2 {
+        (digits[0] * 10 + digits[1]) % 4
+    } else {
+        let sum : usize = digits.iter().enumerate().map(|(i, d)| *d as usize * if i % 2 == 0 {3} else {9}).sum();
+        EAN5_PARITY[sum % 10]
+    };
+    let mut widths = vec![9, 1, 1, 2];
+    for (i, d) in digits.iter().enumerate() {
+        if i > 0 {
+            widths.extend([1, 1]);
+        }
+        widths.extend(digit_widths(*d, parity >> (digits.len() - 1 - i) & 1 == 0));
+    }
+    widths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder_options::DecoderOptions;
+    use crate::test_images::{read_texts, read_widths};
+
+    ///Texts of the codes with the add-on, like "EAN-13 9780306406157 51299".
+    fn read_addons(widths: &[usize], deg: f32) -> Vec<String> {
+        read_widths(widths, deg, &ean_options()).iter().map(|c| format!("{} {} {}", c.symbology, c.text, c.addon.as_deref().unwrap_or("-"))).collect()
+    }
+
+    ///Texts of the codes with the expanded UPC-A, like "UPC-E 01234565 (0012345000065)".
+    fn read_expanded(widths: &[usize], deg: f32) -> Vec<String> {
+        read_widths(widths, deg, &ean_options()).iter().map(|c| format!("{} {} ({})", c.symbology, c.text, c.expanded.as_deref().unwrap_or(""))).collect()
+    }
+
+    fn ean_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::Ean13, Symbology::Ean8, Symbology::UpcE])
+    }
+
+    #[test]
+    fn reads_ean13() {
+        assert_eq!(read_texts(&ean_widths("4006381333931"), 0.0, &ean_options()), vec!["EAN-13 4006381333931"]);
+        assert!(read_texts(&ean_widths("4006381333932"), 0.0, &ean_options()).is_empty());
+    }
+
+    #[test]
+    fn reads_ean8() {
+        assert_eq!(read_texts(&ean_widths("96385074"), 0.0, &ean_options()), vec!["EAN-8 96385074"]);
+        assert!(read_texts(&ean_widths("96385075"), 0.0, &ean_options()).is_empty());
+    }
+
+    #[test]
+    fn ean8_needs_quiet_zone() {
+        let mut widths = vec![1, 2];
+        widths.extend(ean_widths("96385074"));
+        assert!(read_texts(&widths, 0.0, &ean_options()).is_empty());
+    }
+
+    #[test]
+    fn reads_upce() {
+        assert_eq!(read_expanded(&upce_widths("01234565"), 0.0), vec!["UPC-E 01234565 (0012345000065)"]);
+        assert_eq!(read_expanded(&upce_widths("11234562"), 0.0), vec!["UPC-E 11234562 (0112345000062)"]);
+        assert_eq!(read_expanded(&upce_widths("04252614"), 0.0), vec!["UPC-E 04252614 (0042100005264)"]);
+    }
+
+    #[test]
+    fn upce_needs_quiet_zone() {
+        let mut widths = vec![1, 2];
+        widths.extend(upce_widths("01234565"));
+        assert!(read_texts(&widths, 0.0, &ean_options()).is_empty());
+    }
+
+    #[test]
+    fn reads_addons() {
+        let cases = [("9780306406157", "51299"), ("9780306406157", "90000"), ("9771234567003", "12"), ("9771234567003", "05")];
+        for (code, addon) in cases.iter() {
+            let mut widths = ean_widths(code);
+            widths.extend(addon_widths(addon));
+            assert_eq!(read_addons(&widths, 0.0), vec![format!("EAN-13 {} {}", code, addon)]);
+        }
+        let mut widths = upce_widths("01234565");
+        widths.extend(addon_widths("24"));
+        assert_eq!(read_addons(&widths, 0.0), vec!["UPC-E 01234565 24"]);
+    }
+}
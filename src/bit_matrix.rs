@@ -0,0 +1,109 @@
+use std::cmp::{max, min};
+use crate::pixel_sources::GrayImage;
+
+/// Side of the square blocks the binarization threshold is calculated for.
+const BLOCK_SIZE: usize = 8;
+
+/// Blocks with smaller contrast than this are taken as an even color.
+const MIN_BLOCK_CONTRAST: u8 = 16;
+
+/**
+Black and white image for the 2D symbologies, true is a dark pixel or module.
+    width - number of the columns.
+    height - number of the rows.
+    bits - values row by row.
+**/
+#[derive(Clone, Debug)]
+pub struct BitMatrix {
+    pub width: usize,
+    pub height: usize,
+    bits: Vec<bool>,
+}
+
+impl BitMatrix {
+    pub fn new(width: usize, height: usize) -> BitMatrix {
+        BitMatrix { width, height, bits: vec![false; width * height] }
+    }
+
+    ///Value at column x and row y.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.bits[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        self.bits[y * self.width + x] = value;
+    }
+
+    ///Value at column x and row y, false outside of the matrix.
+    pub fn get_checked(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height && self.get(x as usize, y as usize)
+    }
+}
+
+/**
+Converts the image to a black and white matrix.
+Threshold is the average of the 5x5 block neighbourhood, so the uneven lighting of the
+photographed codes does not merge modules. Block without contrast is light, unless it
+is darker than the thresholds of the blocks above and left of it.
+Parameters:
+gray - gray values of the whole image.
+**/
+pub fn binarize_image(gray: &GrayImage) -> BitMatrix {
+    let (width, height) = (gray.width as usize, gray.height as usize);
+    if width == 0 || height == 0 {
+        return BitMatrix::new(width, height);
+    }
+    let values = &gray.values;
+    let blocks_x = width.div_ceil(BLOCK_SIZE);
+    let blocks_y = height.div_ceil(BLOCK_SIZE);
+    let mut averages = vec![0_u8; blocks_x * blocks_y];
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let (mut mn, mut mx, mut sum, mut count) = (255_u8, 0_u8, 0_usize, 0_usize);
+            for y in by * BLOCK_SIZE..min(height, (by + 1) * BLOCK_SIZE) {
+                for v in &values[y * width + bx * BLOCK_SIZE..y * width + min(width, (bx + 1) * BLOCK_SIZE)] {
+                    mn = min(mn, *v);
+                    mx = max(mx, *v);
+                    sum += *v as usize;
+                    count += 1;
+                }
+            }
+            let mut avg = (sum / count) as u8;
+            if mx - mn < MIN_BLOCK_CONTRAST {
+                // Even block is assumed light, unless the neighbours above and left are darker.
+                avg = mn / 2;
+                if by > 0 && bx > 0 {
+                    let neighbours = (averages[(by - 1) * blocks_x + bx] as usize
+                        + 2 * averages[by * blocks_x + bx - 1] as usize
+                        + averages[(by - 1) * blocks_x + bx - 1] as usize) / 4;
+                    if (mn as usize) < neighbours {
+                        avg = neighbours as u8;
+                    }
+                }
+            }
+            averages[by * blocks_x + bx] = avg;
+        }
+    }
+    let mut matrix = BitMatrix::new(width, height);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let mut sum = 0;
+            for ny in by.saturating_sub(2)..=min(blocks_y - 1, by + 2) {
+                for nx in bx.saturating_sub(2)..=min(blocks_x - 1, bx + 2) {
+                    sum += averages[ny * blocks_x + nx] as usize;
+                }
+            }
+            let count = (min(blocks_y - 1, by + 2) + 1 - by.saturating_sub(2))
+                * (min(blocks_x - 1, bx + 2) + 1 - bx.saturating_sub(2));
+            let threshold = (sum / count) as u8;
+            for y in by * BLOCK_SIZE..min(height, (by + 1) * BLOCK_SIZE) {
+                for x in bx * BLOCK_SIZE..min(width, (bx + 1) * BLOCK_SIZE) {
+                    if values[y * width + x] <= threshold {
+                        matrix.set(x, y, true);
+                    }
+                }
+            }
+        }
+    }
+    matrix
+}
@@ -0,0 +1,28 @@
+///Reads bits from the most significant bit of the first byte.
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> BitReader<'a> {
+        BitReader { bytes, pos: 0 }
+    }
+
+    pub fn available(&self) -> usize {
+        self.bytes.len() * 8 - self.pos
+    }
+
+    pub fn read(&mut self, count: usize) -> Option<u32> {
+        if count > self.available() {
+            return None;
+        }
+        let mut value = 0;
+        for _ in 0..count {
+            let bit = self.bytes[self.pos / 8] >> (7 - self.pos % 8) & 1;
+            value = value << 1 | bit as u32;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+}
@@ -0,0 +1,31 @@
+/**
+Byte segment of a 2D symbol as text. Without ECI the bytes are read as UTF-8 if they are valid,
+otherwise as ISO-8859-1. Unknown ECI character sets fail the decoding.
+Parameters:
+bytes - segment bytes.
+eci - ECI assignment number of the character set.
+**/
+pub fn decode_bytes(bytes: &[u8], eci: Option<u32>) -> Option<String> {
+    let encoding = match eci {
+        None => {
+            return Some(match std::str::from_utf8(bytes) {
+                Ok(text) => text.to_string(),
+                Err(_) => bytes.iter().map(|b| *b as char).collect(),
+            });
+        }
+        Some(1) | Some(3) => return Some(bytes.iter().map(|b| *b as char).collect()),
+        Some(part @ 4..=18) => encoding_rs::Encoding::for_label(format!("iso-8859-{}", part - 2).as_bytes())?,
+        Some(20) => encoding_rs::SHIFT_JIS,
+        Some(21) => encoding_rs::WINDOWS_1250,
+        Some(22) => encoding_rs::WINDOWS_1251,
+        Some(23) => encoding_rs::WINDOWS_1252,
+        Some(24) => encoding_rs::WINDOWS_1256,
+        Some(25) => encoding_rs::UTF_16BE,
+        Some(26) | Some(27) => encoding_rs::UTF_8,
+        Some(28) => encoding_rs::BIG5,
+        Some(29) => encoding_rs::GBK,
+        Some(30) => encoding_rs::EUC_KR,
+        _ => return None,
+    };
+    Some(encoding.decode_without_bom_handling(bytes).0.into_owned())
+}
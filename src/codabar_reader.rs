@@ -0,0 +1,184 @@
+use crate::barcode_detector::check_bar_edge;
+use crate::color_line_helpers::{first_bar_index, ColorLine};
+use crate::symbology::{Barcode, Symbology};
+
+const CODABAR_ALPHABET: &[u8; 20] = b"0123456789-$:/.+ABCD";
+
+/// Wide elements of the Codabar characters in CODABAR_ALPHABET order.
+/// First element is in the highest of the 7 bits, bit is set for a wide element.
+const CODABAR_PATTERNS : [u8;20] = [
+    0x03, 0x06, 0x09, 0x60, 0x12, 0x42, 0x21, 0x24, 0x30, 0x48,
+    0x0c, 0x18, 0x45, 0x51, 0x54, 0x15, 0x1a, 0x29, 0x0b, 0x0e,
+];
+
+/// Index of the first start/stop character A in CODABAR_ALPHABET.
+const CODABAR_START_A: usize = 16;
+
+/// Shortest accepted data part, shorter codes are mostly false positives.
+const CODABAR_MIN_DATA: usize = 2;
+
+/**
+Finds Codabar barcodes from one pixel line.
+Parameters:
+color_line - pixel line the crossings were found from, used for the blurred edge correction.
+avg_cross - crossings returned by find_crossings_from_average().
+diffs - widths of the bars and spaces between the crossings.
+start_stop - include the start and stop characters (A-D) in the text.
+check_char - last data character is mod 16 check character, it is validated and removed from the text.
+**/
+pub fn find_codabar(color_line: &ColorLine, avg_cross : &(bool,Vec<usize>), diffs: &[usize], start_stop: bool, check_char: bool) -> Vec<Barcode> {
+    let mut found = Vec::new();
+    // Start, data characters and stop with the gaps between them.
+    let min_len = 8 * (CODABAR_MIN_DATA + 2) - 1;
+    let mut r = first_bar_index(avg_cross);
+    while r + min_len <= diffs.len() {
+        if let Some(CODABAR_START_A..=19) = read_codabar_char(r, color_line, avg_cross) {
+            let char_width: usize = diffs[r..r + 7].iter().sum();
+            let quiet_zone = if r == 0 {avg_cross.1[0]} else {diffs[r - 1]};
+            if quiet_zone * 2 >= char_width {
+                if let Some((mut code, end)) = read_codabar(r, diffs, color_line, avg_cross, start_stop, check_char) {
+                    code.set_span(color_line.point(avg_cross.1[r]), color_line.point(avg_cross.1[end]));
+                    found.push(code);
+                    r = end;
+                    continue;
+                }
+            }
+        }
+        r += 2;
+    }
+    found
+}
+
+/**
+Reads characters starting from the start character at r until the next start/stop character.
+Returns decoded barcode and the index of the element after the stop character.
+**/
+fn read_codabar(mut r: usize, diffs: &[usize], color_line: &ColorLine, avg_cross : &(bool,Vec<usize>), start_stop: bool, check_char: bool) -> Option<(Barcode, usize)> {
+    let mut values : Vec<usize> = Vec::new();
+    loop {
+        if r + 7 > diffs.len() {
+            return None;
+        }
+        let value = read_codabar_char(r, color_line, avg_cross)?;
+        let char_width: usize = diffs[r..r + 7].iter().sum();
+        values.push(value);
+        if value >= CODABAR_START_A && values.len() > 1 {
+            let quiet_zone_ok = r + 7 == diffs.len() || diffs[r + 7] * 2 >= char_width;
+            if !quiet_zone_ok {
+                return None;
+            }
+            r += 7;
+            break;
+        }
+        // Gap between the characters is a narrow space.
+        if r + 7 < diffs.len() && diffs[r + 7] * 3 > char_width {
+            return None;
+        }
+        r += 8;
+    }
+    if check_char {
+        // Sum of all the characters, start and stop included, is divisible by 16.
+        if values.iter().sum::<usize>() % 16 != 0 {
+            return None;
+        }
+        values.remove(values.len() - 2);
+    }
+    if values.len() < CODABAR_MIN_DATA + 2 {
+        return None;
+    }
+    let text_values = if start_stop {&values[..]} else {&values[1..values.len() - 1]};
+    let text = text_values.iter().map(|v| CODABAR_ALPHABET[*v] as char).collect();
+    Some((Barcode::new(Symbology::Codabar, text), r))
+}
+
+/**
+Reads one character from 7 elements starting at r. Returns index in CODABAR_ALPHABET.
+Digits have 2 wide elements, other characters 3, the split with a bigger width jump is used.
+**/
+fn read_codabar_char(r: usize, color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Option<usize> {
+    let lens = edge_corrected_widths(r, 7, color_line, avg_cross);
+    let mut sorted = lens.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let ratio = |wide: usize| sorted[7 - wide] / sorted[6 - wide];
+    let wide = if ratio(2) >= ratio(3) {2} else {3};
+    let wide_min = sorted[7 - wide];
+    // Wide elements have to be clearly wider, ratio is 2:1 to 3:1 in printed codes.
+    if ratio(wide) < 1.25 {
+        return None;
+    }
+    let mut pattern = 0;
+    for l in lens.iter() {
+        pattern <<= 1;
+        if *l >= wide_min {
+            pattern |= 1;
+        }
+    }
+    CODABAR_PATTERNS.iter().position(|p| *p == pattern)
+}
+
+/**
+Element widths from the pixel values. Edge pixels are only partially covered by the element,
+so they are counted by their contrast compared to the darkest or lightest pixel of the element.
+**/
+fn edge_corrected_widths(r: usize, count: usize, color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Vec<f32> {
+    let avg_col = color_line.avg_loc[avg_cross.1[r] / color_line.slice_size];
+    let mut widths = Vec::with_capacity(count);
+    for i in r..r + count {
+        let vals = &color_line.values[avg_cross.1[i]..avg_cross.1[i + 1]];
+        let mut width = vals.len() as f32;
+        if vals.len() > 2 {
+            let edges = check_bar_edge(vals, avg_col);
+            // Element without contrast gives NaN, it is left as it is.
+            if edges.0.is_finite() && edges.1.is_finite() {
+                width += (edges.0.clamp(0.0, 1.0) + edges.1.clamp(0.0, 1.0)) / 2.0 - 1.0;
+            }
+        }
+        widths.push(width.max(0.5));
+    }
+    widths
+}
+
+///Bar and space widths of the text with its start and stop characters, wide elements are 3 modules.
+#[cfg(test)]
+pub(crate) fn codabar_widths(text: &str) -> Vec<usize> {
+    let mut widths = Vec::new();
+    for c in text.bytes() {
+        let pattern = CODABAR_PATTERNS[CODABAR_ALPHABET.iter().position(|a| *a == c).unwrap()];
+        widths.extend((0..7).rev().map(|bit| if pattern >> bit & 1 == 1 {3} else {1}));
+        widths.push(1);
+    }
+    widths.pop();
+    widths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder_options::DecoderOptions;
+    use crate::test_images::read_texts;
+
+    fn codabar_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::Codabar])
+    }
+
+    #[test]
+    fn reads_codabar() {
+        assert_eq!(read_texts(&codabar_widths("A40156B"), 0.0, &codabar_options()), vec!["Codabar 40156"]);
+    }
+
+    #[test]
+    fn reports_start_and_stop() {
+        let options = codabar_options().codabar_start_stop(true);
+        assert_eq!(read_texts(&codabar_widths("A40156B"), 0.0, &options), vec!["Codabar A40156B"]);
+    }
+
+    #[test]
+    fn validates_check_character() {
+        // A=16, 4, 0, 1, 5, 6 and B=17 add up to 49, neither "$" (11) nor "-" (10) makes it divisible by 16.
+        let options = codabar_options().codabar_check_char(true);
+        assert!(read_texts(&codabar_widths("A40156$B"), 0.0, &options).is_empty());
+        assert!(read_texts(&codabar_widths("A40156-B"), 0.0, &options).is_empty());
+        // "/" (13) and the stop D (19) instead of B make it 64.
+        assert_eq!(read_texts(&codabar_widths("A40156/D"), 0.0, &options), vec!["Codabar 40156"]);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::barcode_detector::parse_number_bars;
+use crate::color_line_helpers::{first_bar_index, ColorLine};
+use crate::symbology::{Barcode, Symbology};
+
+const START_A: u8 = 103;
+const START_C: u8 = 105;
+const STOP: u8 = 106;
+
+const FNC3: u8 = 96;
+const FNC2: u8 = 97;
+const SHIFT: u8 = 98;
+const CODE_C: u8 = 99;
+const CODE_B: u8 = 100;
+const CODE_A: u8 = 101;
+const FNC1: u8 = 102;
+
+/// Group separator that replaces FNC1 between GS1 element strings.
+const GS: char = '\u{1d}';
+
+/// Widths of the bars and spaces of Code 128 symbols, 11 modules each.
+/// Stop pattern has an extra bar with width 2 after these 6 elements.
+const CODE128_PATTERNS : [[u8;6];107] = [
+    [2,1,2,2,2,2],[2,2,2,1,2,2],[2,2,2,2,2,1],[1,2,1,2,2,3],[1,2,1,3,2,2],
+    [1,3,1,2,2,2],[1,2,2,2,1,3],[1,2,2,3,1,2],[1,3,2,2,1,2],[2,2,1,2,1,3],
+    [2,2,1,3,1,2],[2,3,1,2,1,2],[1,1,2,2,3,2],[1,2,2,1,3,2],[1,2,2,2,3,1],
+    [1,1,3,2,2,2],[1,2,3,1,2,2],[1,2,3,2,2,1],[2,2,3,2,1,1],[2,2,1,1,3,2],
+    [2,2,1,2,3,1],[2,1,3,2,1,2],[2,2,3,1,1,2],[3,1,2,1,3,1],[3,1,1,2,2,2],
+    [3,2,1,1,2,2],[3,2,1,2,2,1],[3,1,2,2,1,2],[3,2,2,1,1,2],[3,2,2,2,1,1],
+    [2,1,2,1,2,3],[2,1,2,3,2,1],[2,3,2,1,2,1],[1,1,1,3,2,3],[1,3,1,1,2,3],
+    [1,3,1,3,2,1],[1,1,2,3,1,3],[1,3,2,1,1,3],[1,3,2,3,1,1],[2,1,1,3,1,3],
+    [2,3,1,1,1,3],[2,3,1,3,1,1],[1,1,2,1,3,3],[1,1,2,3,3,1],[1,3,2,1,3,1],
+    [1,1,3,1,2,3],[1,1,3,3,2,1],[1,3,3,1,2,1],[3,1,3,1,2,1],[2,1,1,3,3,1],
+    [2,3,1,1,3,1],[2,1,3,1,1,3],[2,1,3,3,1,1],[2,1,3,1,3,1],[3,1,1,1,2,3],
+    [3,1,1,3,2,1],[3,3,1,1,2,1],[3,1,2,1,1,3],[3,1,2,3,1,1],[3,3,2,1,1,1],
+    [3,1,4,1,1,1],[2,2,1,4,1,1],[4,3,1,1,1,1],[1,1,1,2,2,4],[1,1,1,4,2,2],
+    [1,2,1,1,2,4],[1,2,1,4,2,1],[1,4,1,1,2,2],[1,4,1,2,2,1],[1,1,2,2,1,4],
+    [1,1,2,4,1,2],[1,2,2,1,1,4],[1,2,2,4,1,1],[1,4,2,1,1,2],[1,4,2,2,1,1],
+    [2,4,1,2,1,1],[2,2,1,1,1,4],[4,1,3,1,1,1],[2,4,1,1,1,2],[1,3,4,1,1,1],
+    [1,1,1,2,4,2],[1,2,1,1,4,2],[1,2,1,2,4,1],[1,1,4,2,1,2],[1,2,4,1,1,2],
+    [1,2,4,2,1,1],[4,1,1,2,1,2],[4,2,1,1,1,2],[4,2,1,2,1,1],[2,1,2,1,4,1],
+    [2,1,4,1,2,1],[4,1,2,1,2,1],[1,1,1,1,4,3],[1,1,1,3,4,1],[1,3,1,1,4,1],
+    [1,1,4,1,1,3],[1,1,4,3,1,1],[4,1,1,1,1,3],[4,1,1,3,1,1],[1,1,3,1,4,1],
+    [1,1,4,1,3,1],[3,1,1,1,4,1],[4,1,1,1,3,1],[2,1,1,4,1,2],[2,1,1,2,1,4],
+    [2,1,1,2,3,2],[2,3,3,1,1,1],
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum CodeSet {
+    A,
+    B,
+    C,
+}
+
+/**
+Finds Code 128 barcodes from one pixel line.
+Parameters:
+color_line - pixel line the crossings were found from.
+avg_cross - crossings returned by find_crossings_from_average().
+diffs - widths of the bars and spaces between the crossings.
+**/
+pub fn find_code128(color_line: &ColorLine, avg_cross : &(bool,Vec<usize>), diffs: &[usize]) -> Vec<Barcode> {
+    let mut found = Vec::new();
+    // Start, one data symbol, check symbol and stop.
+    let min_len = 6 * 3 + 7;
+    let mut r = first_bar_index(avg_cross);
+    while r + min_len <= diffs.len() {
+        let start = parse_code128_symbol(r, diffs, color_line, avg_cross);
+        if let Some(START_A..=START_C) = start {
+            let unit = diffs[r..r + 6].iter().sum::<usize>() as f32 / 11.0;
+            let quiet_zone = if r == 0 {avg_cross.1[0]} else {diffs[r - 1]};
+            if quiet_zone as f32 >= unit * 5.0 {
+                if let Some((mut code, end)) = read_code128(r, diffs, color_line, avg_cross) {
+                    code.set_span(color_line.point(avg_cross.1[r]), color_line.point(avg_cross.1[end]));
+                    found.push(code);
+                    r = end;
+                    continue;
+                }
+            }
+        }
+        r += 2;
+    }
+    found
+}
+
+/**
+Reads symbols starting from the start symbol at r until the stop pattern.
+Returns decoded barcode and the index of the element after the stop pattern.
+**/
+fn read_code128(mut r: usize, diffs: &[usize], color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Option<(Barcode, usize)> {
+    let mut values = Vec::new();
+    loop {
+        if r + 7 > diffs.len() {
+            return None;
+        }
+        let value = parse_code128_symbol(r, diffs, color_line, avg_cross)?;
+        if value == STOP {
+            let unit = diffs[r..r + 6].iter().sum::<usize>() as f32 / 11.0;
+            let last_bar = diffs[r + 6] as f32 / unit;
+            if !(1.4..=2.6).contains(&last_bar) {
+                return None;
+            }
+            r += 7;
+            break;
+        }
+        if value >= START_A && !values.is_empty() {
+            return None;
+        }
+        values.push(value);
+        r += 6;
+    }
+    // Start, data and check symbol.
+    if values.len() < 3 {
+        return None;
+    }
+    let check = values.pop().unwrap();
+    let mut sum = values[0] as usize;
+    for (i, v) in values.iter().enumerate().skip(1) {
+        sum += i * *v as usize;
+    }
+    if sum % 103 != check as usize {
+        return None;
+    }
+    let (text, gs1) = decode_code128_values(&values)?;
+    let symbology = if gs1 {Symbology::Gs1128} else {Symbology::Code128};
+    Some((Barcode::new(symbology, text), r))
+}
+
+/**
+Decodes symbol values (start symbol included, check symbol excluded) to text.
+FNC1 in the first position marks GS1-128, other FNC1 symbols are returned as GS characters.
+FNC4 adds 128 to the next character, two FNC4 symbols switch the extended mode on or off.
+**/
+fn decode_code128_values(values: &[u8]) -> Option<(String, bool)> {
+    let mut code_set = match values[0] {
+        START_A => CodeSet::A,
+        START_C => CodeSet::C,
+        _ => CodeSet::B,
+    };
+    let mut text = String::new();
+    let mut gs1 = false;
+    let mut shift = false;
+    let mut extended = false;
+    let mut fnc4 = false;
+    let mut last_fnc4 = false;
+    for (pos, value) in values.iter().enumerate().skip(1) {
+        let value = *value;
+        let cur_set = if shift {
+            if code_set == CodeSet::A {CodeSet::B} else {CodeSet::A}
+        } else {
+            code_set
+        };
+        shift = false;
+        let was_fnc4 = last_fnc4;
+        last_fnc4 = false;
+        if value == FNC1 {
+            if pos == 1 {
+                gs1 = true;
+            } else {
+                text.push(GS);
+            }
+            continue;
+        }
+        if cur_set == CodeSet::C {
+            match value {
+                0..=99 => {
+                    text.push((b'0' + value / 10) as char);
+                    text.push((b'0' + value % 10) as char);
+                },
+                CODE_B => code_set = CodeSet::B,
+                CODE_A => code_set = CodeSet::A,
+                _ => return None,
+            }
+            continue;
+        }
+        match value {
+            0..=95 => {
+                let mut ch = if cur_set == CodeSet::A {
+                    if value < 64 {value + 32} else {value - 64}
+                } else {
+                    value + 32
+                };
+                if extended != fnc4 {
+                    ch += 128;
+                }
+                fnc4 = false;
+                text.push(ch as char);
+            },
+            FNC3 | FNC2 => {},
+            SHIFT => shift = true,
+            CODE_C => code_set = CodeSet::C,
+            CODE_B if cur_set == CodeSet::A => code_set = CodeSet::B,
+            CODE_A if cur_set == CodeSet::B => code_set = CodeSet::A,
+            // FNC4 shares the value with the code set change symbol of the current set.
+            CODE_B | CODE_A => {
+                if was_fnc4 {
+                    extended = !extended;
+                    fnc4 = false;
+                } else {
+                    fnc4 = true;
+                    last_fnc4 = true;
+                }
+            },
+            _ => return None,
+        }
+    }
+    Some((text, gs1))
+}
+
+fn parse_code128_symbol(r: usize, diffs: &[usize], color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Option<u8> {
+    let lens = &diffs[r..r + 6];
+    let unit = lens.iter().sum::<usize>() as f32 / 11.0;
+    let s_ix = avg_cross.1[r];
+    let e_ix = avg_cross.1[r + 6];
+    let avg_ix = s_ix / color_line.slice_size;
+    let widths = parse_number_bars(lens, unit, &color_line.values[s_ix..e_ix], color_line.avg_loc[avg_ix], 11, 4);
+    if widths.len() != 6 {
+        return None;
+    }
+    CODE128_PATTERNS.iter().position(|p| p[..] == widths[..]).map(|p| p as u8)
+}
+
+#[cfg(test)]
+pub(crate) const START_B: u8 = 104;
+
+///Widths of the symbols with the check symbol and the stop pattern, the first value is the start symbol.
+#[cfg(test)]
+pub(crate) fn code128_widths(values: &[u8]) -> Vec<usize> {
+    let check = values.iter().enumerate().map(|(i, v)| i.max(1) * *v as usize).sum::<usize>() % 103;
+    let mut widths = Vec::new();
+    for value in values.iter().copied().chain([check as u8, STOP]) {
+        widths.extend(CODE128_PATTERNS[value as usize].iter().map(|w| *w as usize));
+    }
+    widths.push(2);
+    widths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder_options::DecoderOptions;
+    use crate::test_images::read_texts;
+
+    fn code128_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::Code128, Symbology::Gs1128])
+    }
+
+    #[test]
+    fn reads_code128_sets() {
+        // "Code" in set B, shift to set A for a control character and "1234" in set C.
+        let values = [START_B, 35, 79, 68, 69, SHIFT, 72, CODE_C, 12, 34];
+        assert_eq!(read_texts(&code128_widths(&values), 0.0, &code128_options()), vec!["Code 128 Code\u{8}1234"]);
+    }
+
+    #[test]
+    fn reads_gs1_128() {
+        // (01)09501101530003(10)AB1 with FNC1 before the variable length lot number ends.
+        let values = [START_C, FNC1, 1, 9, 50, 11, 1, 53, 0, 3, 10, CODE_B, 33, 34, 17];
+        assert_eq!(read_texts(&code128_widths(&values), 0.0, &code128_options()), vec!["GS1-128 010950110153000310AB1"]);
+        let options = DecoderOptions::default().symbologies(&[Symbology::Code128]);
+        assert!(read_texts(&code128_widths(&values), 0.0, &options).is_empty());
+    }
+
+    #[test]
+    fn rejects_wrong_check_symbol() {
+        let mut widths = code128_widths(&[START_B, 35, 79, 68, 69]);
+        // Check symbol is the second last pattern, it is replaced with the pattern of "A".
+        let check = widths.len() - 13;
+        widths.splice(check..check + 6, CODE128_PATTERNS[33].iter().map(|w| *w as usize));
+        assert!(read_texts(&widths, 0.0, &code128_options()).is_empty());
+    }
+}
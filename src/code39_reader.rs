@@ -0,0 +1,226 @@
+use crate::color_line_helpers::{first_bar_index, ColorLine};
+use crate::symbology::{Barcode, Symbology};
+
+const CODE39_ALPHABET: &[u8; 43] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-. $/+%";
+
+/// Wide elements of the Code 39 characters in CODE39_ALPHABET order.
+/// First element is in the highest of the 9 bits, bit is set for a wide element.
+const CODE39_PATTERNS : [u16;43] = [
+    0x034, 0x121, 0x061, 0x160, 0x031, 0x130, 0x070, 0x025, 0x124, 0x064,
+    0x109, 0x049, 0x148, 0x019, 0x118, 0x058, 0x00d, 0x10c, 0x04c, 0x01c,
+    0x103, 0x043, 0x142, 0x013, 0x112, 0x052, 0x007, 0x106, 0x046, 0x016,
+    0x181, 0x0c1, 0x1c0, 0x091, 0x190, 0x0d0, 0x085, 0x184, 0x0c4, 0x0a8,
+    0x0a2, 0x08a, 0x02a,
+];
+
+/// Start and stop character '*'.
+const CODE39_START_STOP: u16 = 0x094;
+
+/**
+Finds Code 39 barcodes from one pixel line.
+Parameters:
+color_line - pixel line the crossings were found from.
+avg_cross - crossings returned by find_crossings_from_average().
+diffs - widths of the bars and spaces between the crossings.
+check_digit - last character is mod 43 check character, it is validated and removed from the text.
+full_ascii - decode Full ASCII (extended Code 39) shift pairs like "+A" to "a", the decoded codes are Code 39 Extended.
+**/
+pub fn find_code39(color_line: &ColorLine, avg_cross : &(bool,Vec<usize>), diffs: &[usize], check_digit: bool, full_ascii: bool) -> Vec<Barcode> {
+    let mut found = Vec::new();
+    // Start, one data character and stop with the gaps between them.
+    let min_len = 9 * 3 + 2;
+    let mut r = first_bar_index(avg_cross);
+    while r + min_len <= diffs.len() {
+        if read_code39_pattern(&diffs[r..r + 9]) == Some(CODE39_START_STOP) {
+            let char_width: usize = diffs[r..r + 9].iter().sum();
+            let quiet_zone = if r == 0 {avg_cross.1[0]} else {diffs[r - 1]};
+            if quiet_zone * 2 >= char_width {
+                if let Some((mut code, end)) = read_code39(r + 10, diffs, check_digit, full_ascii) {
+                    code.set_span(color_line.point(avg_cross.1[r]), color_line.point(avg_cross.1[end]));
+                    found.push(code);
+                    r = end;
+                    continue;
+                }
+            }
+        }
+        r += 2;
+    }
+    found
+}
+
+/**
+Reads characters after the start character until the stop character.
+r - index of the first bar after the start character and the gap after it.
+Returns decoded barcode and the index of the element after the stop character.
+**/
+fn read_code39(mut r: usize, diffs: &[usize], check_digit: bool, full_ascii: bool) -> Option<(Barcode, usize)> {
+    let mut values : Vec<usize> = Vec::new();
+    loop {
+        if r + 9 > diffs.len() {
+            return None;
+        }
+        let pattern = read_code39_pattern(&diffs[r..r + 9])?;
+        let char_width: usize = diffs[r..r + 9].iter().sum();
+        if pattern == CODE39_START_STOP {
+            let quiet_zone_ok = r + 9 == diffs.len() || diffs[r + 9] * 2 >= char_width;
+            if !quiet_zone_ok {
+                return None;
+            }
+            r += 9;
+            break;
+        }
+        values.push(CODE39_PATTERNS.iter().position(|p| *p == pattern)?);
+        // Gap between the characters is a narrow space.
+        if r + 9 < diffs.len() && diffs[r + 9] * 3 > char_width {
+            return None;
+        }
+        r += 10;
+    }
+    if check_digit {
+        let check = values.pop()?;
+        if values.iter().sum::<usize>() % 43 != check {
+            return None;
+        }
+    }
+    if values.is_empty() {
+        return None;
+    }
+    let text : String = values.iter().map(|v| CODE39_ALPHABET[*v] as char).collect();
+    if full_ascii {
+        if let Some(decoded) = decode_full_ascii(&text).filter(|d| *d != text) {
+            return Some((Barcode::new(Symbology::Code39Extended, decoded), r));
+        }
+    }
+    Some((Barcode::new(Symbology::Code39, text), r))
+}
+
+/**
+Classifies 9 elements to wide and narrow ones. Code 39 character has exactly 3 wide elements.
+Narrow width limit is raised until only 3 elements are wider than it.
+**/
+fn read_code39_pattern(lens: &[usize]) -> Option<u16> {
+    let mut max_narrow = 0;
+    loop {
+        let min_wide = *lens.iter().filter(|l| **l > max_narrow).min()?;
+        max_narrow = min_wide;
+        let mut pattern = 0;
+        let mut wide_count = 0;
+        let mut narrow_max = 0;
+        let mut wide_min = usize::MAX;
+        for l in lens {
+            pattern <<= 1;
+            if *l > max_narrow {
+                pattern |= 1;
+                wide_count += 1;
+                wide_min = wide_min.min(*l);
+            } else {
+                narrow_max = narrow_max.max(*l);
+            }
+        }
+        if wide_count < 3 {
+            return None;
+        }
+        if wide_count == 3 {
+            // Wide elements have to be clearly wider, ratio is 2:1 to 3:1 in printed codes.
+            if wide_min * 4 < narrow_max * 5 {
+                return None;
+            }
+            return Some(pattern);
+        }
+    }
+}
+
+/**
+Full ASCII mode encodes the characters that are missing from Code 39 with two characters.
+Returns None if some shift character is not followed by a valid character.
+**/
+fn decode_full_ascii(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = String::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'+' || c == b'$' || c == b'%' || c == b'/' {
+            let next = *bytes.get(i + 1)?;
+            let ch = match (c, next) {
+                (b'+', b'A'..=b'Z') => next + 32,
+                (b'$', b'A'..=b'Z') => next - 64,
+                (b'%', b'A'..=b'E') => next - 38,
+                (b'%', b'F'..=b'J') => next - 11,
+                (b'%', b'K'..=b'O') => next + 16,
+                (b'%', b'P'..=b'T') => next + 43,
+                (b'%', b'U') => 0,
+                (b'%', b'V') => b'@',
+                (b'%', b'W') => b'`',
+                (b'%', b'X'..=b'Z') => 127,
+                (b'/', b'A'..=b'O') => next - 32,
+                (b'/', b'Z') => b':',
+                _ => return None,
+            };
+            decoded.push(ch as char);
+            i += 2;
+        } else {
+            decoded.push(c as char);
+            i += 1;
+        }
+    }
+    Some(decoded)
+}
+
+///Bar and space widths of the text between the start and stop characters, wide elements are 3 modules.
+#[cfg(test)]
+pub(crate) fn code39_widths(text: &str) -> Vec<usize> {
+    let mut widths = Vec::new();
+    let patterns = text.bytes().map(|c| CODE39_PATTERNS[CODE39_ALPHABET.iter().position(|a| *a == c).unwrap()]);
+    for pattern in std::iter::once(CODE39_START_STOP).chain(patterns).chain(std::iter::once(CODE39_START_STOP)) {
+        widths.extend((0..9).rev().map(|bit| if pattern >> bit & 1 == 1 {3} else {1}));
+        widths.push(1);
+    }
+    widths.pop();
+    widths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder_options::DecoderOptions;
+    use crate::test_images::read_texts;
+
+    fn code39_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::Code39, Symbology::Code39Extended])
+    }
+
+    #[test]
+    fn reads_code39() {
+        assert_eq!(read_texts(&code39_widths("ABC-123"), 0.0, &code39_options()), vec!["Code 39 ABC-123"]);
+    }
+
+    #[test]
+    fn full_ascii_is_off_by_default() {
+        let widths = code39_widths("+H+E+L+L+O");
+        assert_eq!(read_texts(&widths, 0.0, &code39_options()), vec!["Code 39 +H+E+L+L+O"]);
+        assert_eq!(read_texts(&widths, 0.0, &code39_options().code39_full_ascii(true)), vec!["Code 39 Extended hello"]);
+    }
+
+    #[test]
+    fn full_ascii_without_pairs_is_code39() {
+        let options = code39_options().code39_full_ascii(true);
+        assert_eq!(read_texts(&code39_widths("CODE39"), 0.0, &options), vec!["Code 39 CODE39"]);
+    }
+
+    #[test]
+    fn validates_check_character() {
+        // Values of "CODE39" add up to 75, 75 % 43 = 32 is "W".
+        let options = code39_options().code39_check_digit(true);
+        assert_eq!(read_texts(&code39_widths("CODE39W"), 0.0, &options), vec!["Code 39 CODE39"]);
+        assert!(read_texts(&code39_widths("CODE39X"), 0.0, &options).is_empty());
+    }
+
+    #[test]
+    fn code_read_from_one_line_is_dropped_by_the_limit() {
+        // Rows 0 and 50 of the 92 pixels high image, only the second one crosses the bars.
+        let options = code39_options().scan_angles(&[0]).row_step(50);
+        assert_eq!(read_texts(&code39_widths("ABC-123"), 0.0, &options), vec!["Code 39 ABC-123"]);
+        assert!(read_texts(&code39_widths("ABC-123"), 0.0, &options.min_confidence(0.75)).is_empty());
+    }
+}
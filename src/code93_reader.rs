@@ -0,0 +1,223 @@
+use crate::barcode_detector::parse_number_bars;
+use crate::color_line_helpers::{first_bar_index, ColorLine};
+use crate::symbology::{Barcode, Symbology};
+
+/// Characters of the values 0-42, values 43-46 are the shift characters ($), (%), (/) and (+).
+const CODE93_ALPHABET: &[u8; 43] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-. $/+%";
+
+const SHIFT_DOLLAR: u8 = 43;
+const SHIFT_PERCENT: u8 = 44;
+const SHIFT_SLASH: u8 = 45;
+const SHIFT_PLUS: u8 = 46;
+const START_STOP: u8 = 47;
+
+/// Modules of the Code 93 symbols, 9 modules each. First module is in the highest bit, bit is set for a bar.
+/// Stop pattern has an extra termination bar with width 1 after the last symbol.
+const CODE93_PATTERNS : [u16;48] = [
+    0x114, 0x148, 0x144, 0x142, 0x128, 0x124, 0x122, 0x150, 0x112, 0x10a,
+    0x1a8, 0x1a4, 0x1a2, 0x194, 0x192, 0x18a, 0x168, 0x164, 0x162, 0x134,
+    0x11a, 0x158, 0x14c, 0x146, 0x12c, 0x116, 0x1b4, 0x1b2, 0x1ac, 0x1a6,
+    0x196, 0x19a, 0x16c, 0x166, 0x136, 0x13a, 0x12e, 0x1d4, 0x1d2, 0x1ca,
+    0x16e, 0x176, 0x1ae, 0x126, 0x1da, 0x1d6, 0x132, 0x15e,
+];
+
+/**
+Finds Code 93 barcodes from one pixel line.
+Both check characters are validated and removed from the text. Shift characters are decoded
+to full ASCII, codes that use them are reported as Code 93 Extended.
+Parameters:
+color_line - pixel line the crossings were found from.
+avg_cross - crossings returned by find_crossings_from_average().
+diffs - widths of the bars and spaces between the crossings.
+**/
+pub fn find_code93(color_line: &ColorLine, avg_cross : &(bool,Vec<usize>), diffs: &[usize]) -> Vec<Barcode> {
+    let mut found = Vec::new();
+    // Start, one data symbol, two check symbols, stop and termination bar.
+    let min_len = 6 * 5 + 1;
+    let mut r = first_bar_index(avg_cross);
+    while r + min_len <= diffs.len() {
+        if parse_code93_symbol(r, diffs, color_line, avg_cross) == Some(START_STOP) {
+            let unit = diffs[r..r + 6].iter().sum::<usize>() as f32 / 9.0;
+            let quiet_zone = if r == 0 {avg_cross.1[0]} else {diffs[r - 1]};
+            if quiet_zone as f32 >= unit * 5.0 {
+                if let Some((mut code, end)) = read_code93(r + 6, diffs, color_line, avg_cross) {
+                    code.set_span(color_line.point(avg_cross.1[r]), color_line.point(avg_cross.1[end]));
+                    found.push(code);
+                    r = end;
+                    continue;
+                }
+            }
+        }
+        r += 2;
+    }
+    found
+}
+
+/**
+Reads symbols after the start symbol until the stop symbol.
+Returns decoded barcode and the index of the element after the termination bar.
+**/
+fn read_code93(mut r: usize, diffs: &[usize], color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Option<(Barcode, usize)> {
+    let mut values = Vec::new();
+    loop {
+        if r + 7 > diffs.len() {
+            return None;
+        }
+        let value = parse_code93_symbol(r, diffs, color_line, avg_cross)?;
+        if value == START_STOP {
+            let unit = diffs[r..r + 6].iter().sum::<usize>() as f32 / 9.0;
+            let last_bar = diffs[r + 6] as f32 / unit;
+            if !(0.5..=1.6).contains(&last_bar) {
+                return None;
+            }
+            r += 7;
+            break;
+        }
+        values.push(value);
+        r += 6;
+    }
+    // Data and two check symbols.
+    if values.len() < 3 {
+        return None;
+    }
+    let k = values.pop().unwrap();
+    if calc_check(&values, 15) != k {
+        return None;
+    }
+    let c = values.pop().unwrap();
+    if calc_check(&values, 20) != c {
+        return None;
+    }
+    let (text, extended) = decode_code93_values(&values)?;
+    let symbology = if extended {Symbology::Code93Extended} else {Symbology::Code93};
+    Some((Barcode::new(symbology, text), r))
+}
+
+///Check symbol is mod 47 sum of the values weighted 1 to max_weight starting from the last value.
+fn calc_check(values: &[u8], max_weight: usize) -> u8 {
+    let mut sum = 0;
+    for (i, v) in values.iter().rev().enumerate() {
+        sum += (i % max_weight + 1) * *v as usize;
+    }
+    (sum % 47) as u8
+}
+
+/**
+Decodes values to text. Shift characters together with the next character encode full ASCII.
+Returns None if some shift character is not followed by a valid character.
+Second value is true if shift characters were used.
+**/
+fn decode_code93_values(values: &[u8]) -> Option<(String, bool)> {
+    let mut text = String::with_capacity(values.len());
+    let mut extended = false;
+    let mut i = 0;
+    while i < values.len() {
+        let value = values[i];
+        if value < SHIFT_DOLLAR {
+            text.push(CODE93_ALPHABET[value as usize] as char);
+            i += 1;
+            continue;
+        }
+        let next = *CODE93_ALPHABET.get(*values.get(i + 1)? as usize)?;
+        let ch = match (value, next) {
+            (SHIFT_PLUS, b'A'..=b'Z') => next + 32,
+            (SHIFT_DOLLAR, b'A'..=b'Z') => next - 64,
+            (SHIFT_PERCENT, b'A'..=b'E') => next - 38,
+            (SHIFT_PERCENT, b'F'..=b'J') => next - 11,
+            (SHIFT_PERCENT, b'K'..=b'O') => next + 16,
+            (SHIFT_PERCENT, b'P'..=b'T') => next + 43,
+            (SHIFT_PERCENT, b'U') => 0,
+            (SHIFT_PERCENT, b'V') => b'@',
+            (SHIFT_PERCENT, b'W') => b'`',
+            (SHIFT_PERCENT, b'X'..=b'Z') => 127,
+            (SHIFT_SLASH, b'A'..=b'O') => next - 32,
+            (SHIFT_SLASH, b'Z') => b':',
+            _ => return None,
+        };
+        text.push(ch as char);
+        extended = true;
+        i += 2;
+    }
+    Some((text, extended))
+}
+
+fn parse_code93_symbol(r: usize, diffs: &[usize], color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Option<u8> {
+    let lens = &diffs[r..r + 6];
+    let unit = lens.iter().sum::<usize>() as f32 / 9.0;
+    let s_ix = avg_cross.1[r];
+    let e_ix = avg_cross.1[r + 6];
+    let avg_ix = s_ix / color_line.slice_size;
+    let widths = parse_number_bars(lens, unit, &color_line.values[s_ix..e_ix], color_line.avg_loc[avg_ix], 9, 4);
+    if widths.len() != 6 {
+        return None;
+    }
+    let mut pattern: u16 = 0;
+    for (i, w) in widths.iter().enumerate() {
+        for _ in 0..*w {
+            pattern = pattern << 1 | if i % 2 == 0 {1} else {0};
+        }
+    }
+    CODE93_PATTERNS.iter().position(|p| *p == pattern).map(|p| p as u8)
+}
+
+///Widths of the values between the start and the stop symbol, with both check symbols and the termination bar.
+#[cfg(test)]
+pub(crate) fn code93_widths(values: &[u8]) -> Vec<usize> {
+    let mut values = values.to_vec();
+    values.push(calc_check(&values, 20));
+    values.push(calc_check(&values, 15));
+    let mut modules = Vec::new();
+    for value in std::iter::once(START_STOP).chain(values).chain(std::iter::once(START_STOP)) {
+        modules.extend((0..9).rev().map(|bit| CODE93_PATTERNS[value as usize] >> bit & 1 == 1));
+    }
+    modules.push(true);
+    let mut widths = vec![1];
+    for pair in modules.windows(2) {
+        if pair[0] == pair[1] {
+            *widths.last_mut().unwrap() += 1;
+        } else {
+            widths.push(1);
+        }
+    }
+    widths
+}
+
+#[cfg(test)]
+pub(crate) fn code93_values(text: &str) -> Vec<u8> {
+    text.bytes().map(|c| CODE93_ALPHABET.iter().position(|a| *a == c).unwrap() as u8).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder_options::DecoderOptions;
+    use crate::test_images::read_texts;
+
+    fn code93_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::Code93, Symbology::Code93Extended])
+    }
+
+    #[test]
+    fn reads_code93() {
+        assert_eq!(read_texts(&code93_widths(&code93_values("TEST93")), 0.0, &code93_options()), vec!["Code 93 TEST93"]);
+    }
+
+    #[test]
+    fn reads_code93_extended() {
+        // "Ab" with the (+) shift before the lower case b.
+        let values = [10, SHIFT_PLUS, 11];
+        assert_eq!(read_texts(&code93_widths(&values), 0.0, &code93_options()), vec!["Code 93 Extended Ab"]);
+        let options = DecoderOptions::default().symbologies(&[Symbology::Code93]);
+        assert!(read_texts(&code93_widths(&values), 0.0, &options).is_empty());
+    }
+
+    #[test]
+    fn rejects_wrong_check_symbols() {
+        let mut widths = code93_widths(&code93_values("TEST93"));
+        let other = code93_widths(&code93_values("TEST94"));
+        // Check symbols, the stop and the termination bar are the last 19 elements, they are taken from the other text.
+        widths.truncate(widths.len() - 19);
+        widths.extend(&other[other.len() - 19..]);
+        assert!(read_texts(&widths, 0.0, &code93_options()).is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 use std::cmp::max;
 use std::cmp::min;
+use crate::dark_area::Point;
+use crate::decoder_options::DecoderOptions;
 
 ///Holds info about one pixel line in image
 #[derive(Clone, Debug)]
@@ -10,6 +12,7 @@ pub struct ColorLine {
     pub deg: u16,
     pub len: u32,
     pub pos: u32,
+    pub origin: Point,
     pub values: Vec<u8>,
     pub avg_loc: Vec<u8>,
     pub min_loc: Vec<u8>,
@@ -17,7 +20,84 @@ pub struct ColorLine {
     pub slice_size: usize
 }
 
-pub fn find_crossings_from_average(v: &ColorLine, big_image: bool) -> (bool, Vec<usize>){
+impl ColorLine {
+    /**
+    Creates the line from the pixel values and calculates the statistics of the whole line and of every slice.
+    Parameters:
+    values - pixel values along the line.
+    pos - position of the line, the row number for the horizontal lines.
+    deg - angle of the line in degrees.
+    origin - image coordinates of the first value.
+    slice_size - number of the pixels in one slice.
+    **/
+    pub fn from_values(values: Vec<u8>, pos: u32, deg: u16, origin: Point, slice_size: usize) -> ColorLine {
+        let len = values.len() as u32;
+        let mut line = ColorLine {
+            avg: 0,
+            min: 255,
+            max: 0,
+            deg,
+            pos,
+            origin,
+            len,
+            values,
+            avg_loc : Vec::with_capacity((len as usize / slice_size) + 1),
+            min_loc : Vec::with_capacity((len as usize / slice_size) + 1),
+            max_loc : Vec::with_capacity((len as usize / slice_size) + 1),
+            slice_size
+        };
+        let mut line_sum = 0;
+        let mut slc = 0;
+        let mut slice_vals = (255,0,0);
+        for px_code in line.values.iter().cloned() {
+            slice_vals.2 += px_code as usize;
+            line_sum += px_code as usize;
+            if slice_vals.0 > px_code {
+                slice_vals.0 = px_code;
+                if line.min > px_code {
+                    line.min = px_code;
+                }
+            }
+            if slice_vals.1 < px_code {
+                slice_vals.1 = px_code;
+                if line.max < px_code {
+                    line.max = px_code;
+                }
+            }
+
+            slc += 1;
+            if slc >= slice_size {
+                line.min_loc.push(slice_vals.0);
+                line.max_loc.push(slice_vals.1);
+                line.avg_loc.push((slice_vals.2 / slc) as u8);
+                slice_vals = (255,0,0);
+                slc = 0;
+            }
+        }
+        if let Some(slice_avg) = slice_vals.2.checked_div(slc) {
+            line.min_loc.push(slice_vals.0);
+            line.max_loc.push(slice_vals.1);
+            line.avg_loc.push(slice_avg as u8);
+        }
+        line.avg = (line_sum / line.len as usize) as u8;
+        line
+    }
+
+    ///Same line read from the end to the start, its angle is turned by 180 degrees.
+    pub fn reversed(&self) -> ColorLine {
+        let values = self.values.iter().rev().cloned().collect();
+        let origin = self.point(self.values.len().saturating_sub(1));
+        ColorLine::from_values(values, self.pos, (self.deg + 180) % 360, origin, self.slice_size)
+    }
+
+    ///Image coordinates of the value at the index, the values are one pixel apart along the line.
+    pub fn point(&self, index: usize) -> Point {
+        let (sin, cos) = (self.deg as f32).to_radians().sin_cos();
+        (self.origin.0 + cos * index as f32, self.origin.1 + sin * index as f32)
+    }
+}
+
+pub fn find_crossings_from_average(v: &ColorLine, big_image: bool, options: &DecoderOptions) -> (bool, Vec<usize>){
 
     let mut c_arr: (bool,Vec<usize>) = (true,Vec::new());
     let mut cur= true;
@@ -27,24 +107,24 @@ pub fn find_crossings_from_average(v: &ColorLine, big_image: bool) -> (bool, Vec
         c_arr.0 = false;
         cur = true;
     }
-    let buf = max(3,((cur_stat.1 -cur_stat.0) as f32 * 0.04) as u8);
+    let buf = max(3,((cur_stat.1 -cur_stat.0) as f32 * options.threshold_buffer) as u8);
     let mut range = (cur_stat.2.saturating_sub(buf),cur_stat.2.saturating_add(buf),0);
 
     let mut slc = v.slice_size/2;
     let mut num = 0;
-    if v.max_loc[cur_loc] - v.min_loc[cur_loc] < 16 {
-        range = find_range_buffer(cur_loc,v);
+    if v.max_loc[cur_loc] - v.min_loc[cur_loc] < options.contrast_floor {
+        range = find_range_buffer(cur_loc,v,options);
         cur_loc = range.2;
         num = slc + v.slice_size * (cur_loc-1);
     }
 
-    let in_buffer_max_len = if (big_image) {7} else {3};
+    let in_buffer_max_len = if big_image {7} else {3};
 
     let mut buf_buffer = (0,cur);
     let mut col;
     while num < v.values.len(){
         col = &v.values[num];
-        if cur == true {
+        if cur {
             if *col < range.0 {
                 cur = false;
                 if big_image {
@@ -72,10 +152,10 @@ pub fn find_crossings_from_average(v: &ColorLine, big_image: bool) -> (bool, Vec
         num += 1;
         slc += 1;
         if slc >= v.slice_size {
-            range = find_range_buffer(cur_loc,v);
+            range = find_range_buffer(cur_loc,v,options);
             let diff = range.2 - cur_loc;
             if diff > 1 {
-                num = num + v.slice_size * diff;
+                num += v.slice_size * diff;
             }
             cur_loc = range.2;
             slc = 0;
@@ -95,7 +175,7 @@ fn compare_row_value_with_buffer(
     if val1 <= val2 {
         buf_buffer.0 += 1;
     } else {
-        if buf_buffer.1 == true && buf_buffer.0 > in_buffer_max_len {
+        if buf_buffer.1 && buf_buffer.0 > in_buffer_max_len {
             c_arr.1.push(num - buf_buffer.0);
             c_arr.1.push(num);
         }
@@ -104,7 +184,7 @@ fn compare_row_value_with_buffer(
     }
 }
 
-fn find_range_buffer(mut cur: usize,v: &ColorLine) -> (u8,u8,usize){
+fn find_range_buffer(mut cur: usize,v: &ColorLine,options: &DecoderOptions) -> (u8,u8,usize){
     let mut next = cur+1;
     let len = v.max_loc.len();
     let mut avg : u8 = 0;
@@ -113,7 +193,7 @@ fn find_range_buffer(mut cur: usize,v: &ColorLine) -> (u8,u8,usize){
     while next < len{
         mx = max(v.max_loc[cur],v.max_loc[next]);
         mn = min(v.min_loc[cur],v.min_loc[next]);
-        if mx - mn < 16 {
+        if mx - mn < options.contrast_floor {
             cur += 1;
             next = cur + 1;
             continue;
@@ -135,7 +215,24 @@ fn find_range_buffer(mut cur: usize,v: &ColorLine) -> (u8,u8,usize){
     }
     avg = ((avg as u32 + (mx as u32 + mn as u32)/2) / 2) as u8;
 
-    let buf = max(3,((mx -mn) as f32 * 0.04) as u8);
+    let buf = max(3,((mx -mn) as f32 * options.threshold_buffer) as u8);
     let range = (avg.saturating_sub(buf),avg.saturating_add(buf),next);
     return range;
-}
\ No newline at end of file
+}
+///Widths of the bars and spaces between the crossings.
+pub fn crossing_widths(crossings: &[usize]) -> Vec<usize>{
+    let mut diffs : Vec<usize> = Vec::with_capacity(crossings.len());
+    for w in crossings.windows(2) {
+        diffs.push(w[1] - w[0]);
+    }
+    return diffs;
+}
+
+/**
+Index of the first bar in crossings widths.
+Line that starts with a light color has its first crossing on a bar,
+inverted barcodes have light bars on dark background.
+**/
+pub fn first_bar_index(avg_cross : &(bool,Vec<usize>)) -> usize{
+    if avg_cross.0 {0} else {1}
+}
@@ -0,0 +1,126 @@
+use crate::bit_matrix::BitMatrix;
+
+/// Point in the image coordinates, pixel (x, y) covers the square from (x, y) to (x + 1, y + 1).
+pub type Point = (f32, f32);
+
+/**
+Connected dark pixels of the binarized image.
+    width, height - size of the bounding box in pixels.
+    hull - convex hull of the outer pixel corners.
+**/
+#[derive(Clone, Debug)]
+pub struct DarkArea {
+    pub width: usize,
+    pub height: usize,
+    pub hull: Vec<Point>,
+}
+
+/**
+Fills the 8-connected dark area that contains the starting pixel.
+Filled pixels are marked to visited, so every area is handled only once.
+Parameters:
+image - binarized image.
+visited - pixels that already belong to some filled area, same size as the image.
+x, y - dark starting pixel.
+**/
+pub fn fill_dark_area(image: &BitMatrix, visited: &mut BitMatrix, x: usize, y: usize) -> DarkArea {
+    // Leftmost and rightmost pixel of every row, starting from the row top.
+    let mut top = y;
+    let mut extents: Vec<(usize,usize)> = Vec::new();
+    let mut stack = vec![(x, y)];
+    visited.set(x, y, true);
+    while let Some((px, py)) = stack.pop() {
+        if py < top {
+            extents.splice(0..0, vec![(usize::MAX, 0); top - py]);
+            top = py;
+        }
+        let row = py - top;
+        if row >= extents.len() {
+            extents.resize(row + 1, (usize::MAX, 0));
+        }
+        let extent = &mut extents[row];
+        *extent = (extent.0.min(px), extent.1.max(px));
+        for ny in py.saturating_sub(1)..(py + 2).min(image.height) {
+            for nx in px.saturating_sub(1)..(px + 2).min(image.width) {
+                if image.get(nx, ny) && !visited.get(nx, ny) {
+                    visited.set(nx, ny, true);
+                    stack.push((nx, ny));
+                }
+            }
+        }
+    }
+    let left = extents.iter().map(|e| e.0).min().unwrap_or(x);
+    let right = extents.iter().map(|e| e.1).max().unwrap_or(x);
+    let mut points = Vec::with_capacity(extents.len() * 4);
+    for (row, (min_x, max_x)) in extents.iter().enumerate() {
+        let row_top = (top + row) as f32;
+        let (min_x, max_x) = (*min_x as f32, *max_x as f32 + 1.0);
+        points.extend_from_slice(&[(min_x, row_top), (min_x, row_top + 1.0), (max_x, row_top), (max_x, row_top + 1.0)]);
+    }
+    DarkArea { width: right + 1 - left, height: extents.len(), hull: convex_hull(points) }
+}
+
+///Convex hull with the monotone chain algorithm.
+fn convex_hull(mut points: Vec<Point>) -> Vec<Point> {
+    points.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut hull: Vec<Point> = Vec::with_capacity(points.len() * 2);
+    for pass in 0..2 {
+        let start = hull.len();
+        for p in points.iter() {
+            while hull.len() >= start + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], *p) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(*p);
+        }
+        hull.pop();
+        if pass == 0 {
+            points.reverse();
+        }
+    }
+    hull
+}
+
+///Cross product of the vectors o->a and o->b, positive if b is clockwise from a in the image coordinates.
+pub fn cross(o: Point, a: Point, b: Point) -> f32 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+pub fn distance(a: Point, b: Point) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+pub fn lerp(a: Point, b: Point, t: f32) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+pub fn is_dark(image: &BitMatrix, p: Point) -> bool {
+    image.get_checked(p.0.floor() as i32, p.1.floor() as i32)
+}
+
+/**
+Four extreme points of the hull: the farthest pair and the farthest points on both sides of the
+line between them. For a square or rectangular area these are its corners in the counterclockwise order.
+**/
+pub fn hull_quadrilateral(hull: &[Point]) -> Option<[Point; 4]> {
+    let mut best = (0.0, 0, 0);
+    for i in 0..hull.len() {
+        for j in i + 1..hull.len() {
+            let d = distance(hull[i], hull[j]);
+            if d > best.0 {
+                best = (d, i, j);
+            }
+        }
+    }
+    let (p, q) = (hull[best.1], hull[best.2]);
+    let side = |sign: f32| hull.iter().cloned()
+        .max_by(|a, b| (sign * cross(p, q, *a)).partial_cmp(&(sign * cross(p, q, *b))).unwrap_or(std::cmp::Ordering::Equal));
+    let (r1, r2) = (side(1.0)?, side(-1.0)?);
+    if cross(p, q, r1) <= 0.0 || cross(p, q, r2) >= 0.0 {
+        return None;
+    }
+    Some([p, r1, q, r2])
+}
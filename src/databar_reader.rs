@@ -0,0 +1,799 @@
+use crate::barcode_detector::parse_number_bars;
+use crate::color_line_helpers::{first_bar_index, ColorLine};
+use crate::itf_reader::calc_mod10_check;
+use crate::symbology::{Barcode, Symbology};
+
+/// First three elements of the RSS-14 finder patterns, last two elements are always 1.
+const RSS14_FINDERS : [[u8;3];9] = [
+    [3,8,2], [3,5,5], [3,3,7], [3,1,9], [2,7,4], [2,5,6], [2,3,8], [1,5,7], [1,3,9],
+];
+
+/// First three elements of the DataBar Expanded finder patterns A-F.
+const EXPANDED_FINDERS : [[u8;3];6] = [
+    [1,8,4], [3,6,4], [3,4,6], [3,2,8], [2,6,5], [2,2,9],
+];
+
+/// Finder patterns of the Expanded symbols by the number of character pairs, starting from 2 pairs.
+const EXPANDED_SEQUENCES : [&[u8];10] = [
+    &[0,0],
+    &[0,1,1],
+    &[0,2,1,3],
+    &[0,4,1,3,2],
+    &[0,4,1,3,3,5],
+    &[0,4,1,3,4,5,5],
+    &[0,0,1,1,2,2,3,3],
+    &[0,0,1,1,2,2,3,4,4],
+    &[0,0,1,1,2,2,3,4,5,5],
+    &[0,0,1,1,2,3,3,4,4,5,5],
+];
+
+// Character groups of the RSS-14 outside (16 modules) and inside (15 modules) characters.
+const OUTSIDE_EVEN_TOTAL_SUBSET : [usize;5] = [1, 10, 34, 70, 126];
+const OUTSIDE_GSUM : [usize;6] = [0, 161, 961, 2015, 2715, 2841];
+const OUTSIDE_ODD_WIDEST : [u8;5] = [8, 6, 4, 3, 1];
+const INSIDE_ODD_TOTAL_SUBSET : [usize;4] = [4, 20, 48, 81];
+const INSIDE_GSUM : [usize;5] = [0, 336, 1036, 1516, 1597];
+const INSIDE_ODD_WIDEST : [u8;4] = [2, 4, 6, 8];
+
+// Character groups of the Expanded characters (17 modules).
+const EXPANDED_EVEN_TOTAL_SUBSET : [usize;5] = [4, 20, 52, 104, 204];
+const EXPANDED_GSUM : [usize;6] = [0, 348, 1388, 2948, 3988, 4192];
+const EXPANDED_ODD_WIDEST : [u8;5] = [7, 5, 4, 3, 1];
+
+/// Elements between the finder patterns of two Expanded pairs.
+const EXPANDED_PAIR_LEN: usize = 21;
+
+/// Group separator that replaces FNC1 between GS1 element strings.
+const GS: char = '\u{1d}';
+
+/**
+Half of an RSS-14 symbol: outside character, finder pattern and inside character.
+    right - right half, it is read in the reversed direction.
+    value - 1597 * outside value + inside value.
+    checksum - outside checksum + 4 * inside checksum.
+    row, start, end - row and pixel range of the half, used for pairing halves from different rows.
+**/
+#[derive(Clone, PartialEq)]
+pub struct Rss14Half {
+    right: bool,
+    value: usize,
+    checksum: usize,
+    finder: usize,
+    row: u32,
+    start: usize,
+    end: usize,
+}
+
+/**
+Two Expanded data characters with the finder pattern between them.
+Characters are (value, checksum) pairs. Right character is missing from the last pair of some symbols.
+**/
+#[derive(Clone, PartialEq)]
+pub struct ExpandedPair {
+    finder: u8,
+    forward: bool,
+    left: (usize, usize),
+    right: Option<(usize, usize)>,
+}
+
+/**
+DataBar parts seen on the previous rows. Stacked symbols have their halves or
+Expanded segments on separate rows, they are combined like the partial EAN codes.
+**/
+#[derive(Default)]
+pub struct DataBarRows {
+    halves: Vec<Rss14Half>,
+    segments: Vec<Vec<ExpandedPair>>,
+}
+
+///RSS-14 half or Expanded segment read from one pixel line, before it is combined with the other rows.
+pub enum DataBarPart {
+    Half(Rss14Half),
+    Segment(Vec<ExpandedPair>),
+}
+
+/**
+Reads GS1 DataBar (RSS-14, Stacked) halves and DataBar Expanded (also Stacked) segments from one pixel line.
+The line is read on its own, the parts are combined into barcodes by combine_databar_parts().
+DataBar Limited is not supported, its check character patterns are not included.
+Parameters:
+color_line - pixel line the crossings were found from.
+avg_cross - crossings returned by find_crossings_from_average().
+diffs - widths of the bars and spaces between the crossings.
+**/
+pub fn read_databar_parts(color_line: &ColorLine, avg_cross : &(bool,Vec<usize>), diffs: &[usize]) -> Vec<DataBarPart> {
+    let mut parts = Vec::new();
+    let mut j = 0;
+    while j + 5 <= diffs.len() {
+        match read_finder(j, diffs, color_line, avg_cross) {
+            Some((false, finder, forward)) => {
+                if let Some(half) = read_rss14_half(j, finder, forward, diffs, color_line, avg_cross) {
+                    parts.push(DataBarPart::Half(half));
+                    j += 13;
+                    continue;
+                }
+            },
+            Some((true, _, _)) => {
+                if let Some((segment, end)) = read_expanded_segment(j, diffs, color_line, avg_cross) {
+                    parts.push(DataBarPart::Segment(segment));
+                    j = end;
+                    continue;
+                }
+            },
+            None => {},
+        }
+        j += 1;
+    }
+    parts
+}
+
+/**
+Decodes the DataBar parts of one line, the halves and segments are also combined with the ones found on the previous rows.
+Parameters:
+parts - parts returned by read_databar_parts(), in the order of the line.
+rows - halves and segments found from the previous rows.
+**/
+pub fn combine_databar_parts(parts: Vec<DataBarPart>, rows: &mut DataBarRows) -> Vec<Barcode> {
+    let mut found = Vec::new();
+    for part in parts {
+        match part {
+            DataBarPart::Half(half) => found.append(&mut pair_rss14_half(half, rows)),
+            DataBarPart::Segment(segment) => found.append(&mut add_expanded_segment(segment, rows)),
+        }
+    }
+    found
+}
+
+/**
+Reads the finder pattern at j. Returns (expanded, finder value, forward).
+Forward finder pattern starts with a space, reversed one ends with a bar.
+**/
+fn read_finder(j: usize, diffs: &[usize], color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Option<(bool, usize, bool)> {
+    let widths = read_modules(j, 5, 15, 9, diffs, color_line, avg_cross)?;
+    let is_bar = j % 2 == first_bar_index(avg_cross);
+    let key = if !is_bar && widths[3] == 1 && widths[4] == 1 {
+        [widths[0], widths[1], widths[2]]
+    } else if is_bar && widths[0] == 1 && widths[1] == 1 {
+        [widths[4], widths[3], widths[2]]
+    } else {
+        return None;
+    };
+    if let Some(finder) = RSS14_FINDERS.iter().position(|f| *f == key) {
+        return Some((false, finder, !is_bar));
+    }
+    EXPANDED_FINDERS.iter().position(|f| *f == key).map(|finder| (true, finder, !is_bar))
+}
+
+///Module widths of count elements starting from r, returns None if they can not be split to the given modules.
+fn read_modules(r: usize, count: usize, modules: u8, max_len: u8, diffs: &[usize], color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Option<Vec<u8>> {
+    if r + count > diffs.len() {
+        return None;
+    }
+    let lens = &diffs[r..r + count];
+    let unit = lens.iter().sum::<usize>() as f32 / modules as f32;
+    let s_ix = avg_cross.1[r];
+    let e_ix = avg_cross.1[r + count];
+    let avg_ix = s_ix / color_line.slice_size;
+    let widths = parse_number_bars(lens, unit, &color_line.values[s_ix..e_ix], color_line.avg_loc[avg_ix], modules, max_len);
+    if widths.len() != count {
+        return None;
+    }
+    Some(widths)
+}
+
+/**
+Module widths of the data character starting from r, in the order of the element counters.
+Counters start from the element next to the finder for the characters before the finder
+and from the outermost element for the characters after the finder.
+**/
+fn read_char_counters(r: usize, modules: u8, reversed: bool, diffs: &[usize], color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Option<[u8;8]> {
+    let widths = read_modules(r, 8, modules, 8, diffs, color_line, avg_cross)?;
+    let mut counters = [0;8];
+    for (i, w) in widths.iter().enumerate() {
+        counters[if reversed {7 - i} else {i}] = *w;
+    }
+    Some(counters)
+}
+
+/**
+Reads the RSS-14 half around the finder at j. Left half has the outside character before the
+forward finder pattern, right half has it after the reversed finder pattern.
+**/
+fn read_rss14_half(j: usize, finder: usize, forward: bool, diffs: &[usize], color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Option<Rss14Half> {
+    if j < 8 || j + 13 > diffs.len() {
+        return None;
+    }
+    let (outside, inside) = if forward {
+        (read_char_counters(j - 8, 16, true, diffs, color_line, avg_cross)?,
+         read_char_counters(j + 5, 15, true, diffs, color_line, avg_cross)?)
+    } else {
+        (read_char_counters(j + 5, 16, false, diffs, color_line, avg_cross)?,
+         read_char_counters(j - 8, 15, false, diffs, color_line, avg_cross)?)
+    };
+    let outside = decode_rss14_char(&outside, true)?;
+    let inside = decode_rss14_char(&inside, false)?;
+    Some(Rss14Half {
+        right: !forward,
+        value: 1597 * outside.0 + inside.0,
+        checksum: outside.1 + 4 * inside.1,
+        finder,
+        row: color_line.pos,
+        start: avg_cross.1[j - 8],
+        end: avg_cross.1[j + 13],
+    })
+}
+
+/**
+Pairs the half with the opposite halves found from the same or previous rows.
+Halves of stacked symbols are on different rows, omnidirectional symbols have both halves on one row.
+**/
+fn pair_rss14_half(half: Rss14Half, rows: &mut DataBarRows) -> Vec<Barcode> {
+    let mut found = Vec::new();
+    let width = half.end - half.start;
+    for other in rows.halves.iter().filter(|h| h.right != half.right) {
+        let (left, right) = if half.right {(other, &half)} else {(&half, other)};
+        let row_diff = (half.row as i64 - other.row as i64).unsigned_abs() as usize;
+        let position_ok = if row_diff == 0 {
+            left.end <= right.start + width / 8
+        } else {
+            row_diff <= width && (left.start as i64 - right.start as i64).unsigned_abs() as usize <= width
+        };
+        if position_ok && check_rss14_checksum(left, right) {
+            let value = 4537077 * left.value + right.value;
+            let digits : Vec<u8> = format!("{:013}", value).bytes().map(|b| b - b'0').collect();
+            let mut text : String = digits.iter().map(|d| (b'0' + *d) as char).collect();
+            text.push((b'0' + calc_mod10_check(&digits)) as char);
+            found.push(Barcode::new(Symbology::DataBar, text));
+        }
+    }
+    rows.halves.retain(|h| h.right != half.right || h.value != half.value || h.finder != half.finder);
+    rows.halves.push(half);
+    found
+}
+
+///Checksum of the halves is encoded by the finder patterns.
+fn check_rss14_checksum(left: &Rss14Half, right: &Rss14Half) -> bool {
+    let check = (left.checksum + 16 * right.checksum) % 79;
+    let mut target = 9 * left.finder + right.finder;
+    if target > 72 {
+        target -= 1;
+    }
+    if target > 8 {
+        target -= 1;
+    }
+    check == target
+}
+
+///Splits counters to odd and even elements.
+fn odd_even_counts(counters: &[u8;8]) -> ([u8;4], [u8;4]) {
+    let mut odd = [0;4];
+    let mut even = [0;4];
+    for i in 0..4 {
+        odd[i] = counters[2 * i];
+        even[i] = counters[2 * i + 1];
+    }
+    (odd, even)
+}
+
+///Returns (value, checksum) of the RSS-14 data character.
+fn decode_rss14_char(counters: &[u8;8], outside: bool) -> Option<(usize, usize)> {
+    let (odd, even) = odd_even_counts(counters);
+    let mut checksum = 0;
+    for i in (0..4).rev() {
+        checksum = checksum * 9 + odd[i] as usize + 3 * even[i] as usize;
+    }
+    let odd_sum : u8 = odd.iter().sum();
+    let even_sum : u8 = even.iter().sum();
+    let (value, next_gsum) = if outside {
+        if odd_sum % 2 == 1 || !(4..=12).contains(&odd_sum) {
+            return None;
+        }
+        let group = ((12 - odd_sum) / 2) as usize;
+        let odd_widest = OUTSIDE_ODD_WIDEST[group];
+        let v_odd = rss_value(&odd, odd_widest, false)?;
+        let v_even = rss_value(&even, 9 - odd_widest, true)?;
+        let t_even = OUTSIDE_EVEN_TOTAL_SUBSET[group];
+        if v_even >= t_even {
+            return None;
+        }
+        (v_odd * t_even + v_even + OUTSIDE_GSUM[group], OUTSIDE_GSUM[group + 1])
+    } else {
+        if even_sum % 2 == 1 || !(4..=10).contains(&even_sum) {
+            return None;
+        }
+        let group = ((10 - even_sum) / 2) as usize;
+        let odd_widest = INSIDE_ODD_WIDEST[group];
+        let v_odd = rss_value(&odd, odd_widest, true)?;
+        let v_even = rss_value(&even, 9 - odd_widest, false)?;
+        let t_odd = INSIDE_ODD_TOTAL_SUBSET[group];
+        if v_odd >= t_odd {
+            return None;
+        }
+        (v_even * t_odd + v_odd + INSIDE_GSUM[group], INSIDE_GSUM[group + 1])
+    };
+    if value >= next_gsum {
+        return None;
+    }
+    Some((value, checksum))
+}
+
+/**
+Reads Expanded pairs starting from the finder at j while the next finder follows the pair.
+Returns the pairs and the index of the element after the last pair.
+**/
+fn read_expanded_segment(mut j: usize, diffs: &[usize], color_line: &ColorLine, avg_cross : &(bool,Vec<usize>)) -> Option<(Vec<ExpandedPair>, usize)> {
+    let mut pairs = Vec::new();
+    loop {
+        let (expanded, finder, forward) = read_finder(j, diffs, color_line, avg_cross)?;
+        if !expanded || j < 8 {
+            break;
+        }
+        let left = read_char_counters(j - 8, 17, true, diffs, color_line, avg_cross)
+            .and_then(|c| decode_expanded_char(&c, finder as u8, forward, true));
+        let left = match left {
+            Some(left) => left,
+            None => break,
+        };
+        let right = read_char_counters(j + 5, 17, true, diffs, color_line, avg_cross)
+            .and_then(|c| decode_expanded_char(&c, finder as u8, forward, false));
+        pairs.push(ExpandedPair {finder: finder as u8, forward, left, right});
+        if right.is_none() || j + EXPANDED_PAIR_LEN + 5 > diffs.len() {
+            j += 5;
+            break;
+        }
+        j += EXPANDED_PAIR_LEN;
+        if !matches!(read_finder(j, diffs, color_line, avg_cross), Some((true, _, _))) {
+            j -= 8;
+            break;
+        }
+    }
+    if pairs.is_empty() {
+        return None;
+    }
+    Some((pairs, j))
+}
+
+/**
+Decodes the segment if it is a full symbol. Otherwise the segment is stored and
+combined with the segments of the previous rows, stacked symbols have a few pairs on every row.
+**/
+fn add_expanded_segment(segment: Vec<ExpandedPair>, rows: &mut DataBarRows) -> Vec<Barcode> {
+    if let Some(text) = decode_expanded(&segment) {
+        return vec![Barcode::new(Symbology::DataBarExpanded, text)];
+    }
+    if rows.segments.last() != Some(&segment) {
+        rows.segments.retain(|s| *s != segment);
+        rows.segments.push(segment);
+    }
+    let max_rows = EXPANDED_SEQUENCES.len();
+    if rows.segments.len() > max_rows {
+        rows.segments.remove(0);
+    }
+    let mut found = Vec::new();
+    for first in (0..rows.segments.len().saturating_sub(1)).rev() {
+        //Rows of a stacked symbol arrive from the last to the first when it is upside down.
+        let pairs : Vec<ExpandedPair> = rows.segments[first..].concat();
+        let reversed_pairs : Vec<ExpandedPair> = rows.segments[first..].iter().rev().flatten().cloned().collect();
+        if let Some(text) = decode_expanded(&pairs).or_else(|| decode_expanded(&reversed_pairs)) {
+            found.push(Barcode::new(Symbology::DataBarExpanded, text));
+            break;
+        }
+    }
+    found
+}
+
+/**
+Returns (value, checksum) of the Expanded data character. Checksum weights depend on the finder,
+its direction and the side of the character. The first character is the check character, it has no weight.
+**/
+fn decode_expanded_char(counters: &[u8;8], finder: u8, forward: bool, left: bool) -> Option<(usize, usize)> {
+    let (odd, even) = odd_even_counts(counters);
+    let mut checksum = 0;
+    if !(finder == 0 && forward && left) {
+        let row = 4 * finder as usize + if forward {0} else {2} + if left {0} else {1} - 1;
+        for i in 0..4 {
+            checksum += odd[i] as usize * expanded_weight(row * 8 + 2 * i);
+            checksum += even[i] as usize * expanded_weight(row * 8 + 2 * i + 1);
+        }
+    }
+    let odd_sum : u8 = odd.iter().sum();
+    if odd_sum % 2 == 1 || !(4..=12).contains(&odd_sum) {
+        return None;
+    }
+    let group = ((12 - odd_sum) / 2) as usize;
+    let odd_widest = EXPANDED_ODD_WIDEST[group];
+    let v_odd = rss_value(&odd, odd_widest, true)?;
+    let v_even = rss_value(&even, 9 - odd_widest, false)?;
+    let t_even = EXPANDED_EVEN_TOTAL_SUBSET[group];
+    let value = v_odd * t_even + v_even + EXPANDED_GSUM[group];
+    if v_even >= t_even || value >= EXPANDED_GSUM[group + 1] {
+        return None;
+    }
+    Some((value, checksum))
+}
+
+///Checksum weights of the Expanded characters are the powers of 3 modulo 211.
+fn expanded_weight(n: usize) -> usize {
+    let mut weight = 1;
+    for _ in 0..n {
+        weight = weight * 3 % 211;
+    }
+    weight
+}
+
+/**
+Checks the finder sequence and the check character of the pairs and decodes them.
+Returns None if the pairs are not a complete symbol.
+**/
+fn decode_expanded(pairs: &[ExpandedPair]) -> Option<String> {
+    if pairs.len() < 2 || pairs.len() > EXPANDED_SEQUENCES.len() + 1 {
+        return None;
+    }
+    let sequence = EXPANDED_SEQUENCES[pairs.len() - 2];
+    for (i, pair) in pairs.iter().enumerate() {
+        if pair.finder != sequence[i] || pair.forward != (i % 2 == 0) {
+            return None;
+        }
+        if pair.right.is_none() && i + 1 < pairs.len() {
+            return None;
+        }
+    }
+    let mut chars = Vec::with_capacity(pairs.len() * 2);
+    for pair in pairs.iter() {
+        chars.push(pair.left);
+        if let Some(right) = pair.right {
+            chars.push(right);
+        }
+    }
+    // The smallest symbol has the check character and 3 data characters.
+    if chars.len() < 4 {
+        return None;
+    }
+    let checksum : usize = chars[1..].iter().map(|c| c.1).sum();
+    if chars[0].0 != 211 * (chars.len() - 4) + checksum % 211 {
+        return None;
+    }
+    let mut bits = Vec::with_capacity(chars.len() * 12);
+    for c in chars[1..].iter() {
+        for b in (0..12).rev() {
+            bits.push(c.0 & (1 << b) != 0);
+        }
+    }
+    decode_expanded_bits(&bits)
+}
+
+fn extract(bits: &[bool], pos: usize, len: usize) -> usize {
+    let mut value = 0;
+    for b in bits[pos..pos + len].iter() {
+        value = value << 1 | *b as usize;
+    }
+    value
+}
+
+/**
+Decodes the Expanded data bits to a GS1 element string. FNC1 after variable length fields
+is returned as GS character, same as in GS1-128.
+Compressed methods encode GTIN (01) with weight (310x, 320x), price (392x, 393x) or date fields.
+**/
+fn decode_expanded_bits(bits: &[bool]) -> Option<String> {
+    let size = bits.len();
+    let mut text = String::new();
+    if size < 5 {
+        return None;
+    }
+    if bits[1] {
+        if size < 48 {
+            return None;
+        }
+        let first = extract(bits, 4, 4);
+        if first > 9 {
+            return None;
+        }
+        text += &compressed_gtin(bits, 8, first as u8)?;
+        decode_general_purpose(bits, 48, &mut text)?;
+        return Some(text);
+    }
+    if !bits[2] {
+        decode_general_purpose(bits, 5, &mut text)?;
+        return Some(text);
+    }
+    let method = extract(bits, 1, 4);
+    if method == 4 || method == 5 {
+        if size != 60 {
+            return None;
+        }
+        text += &compressed_gtin(bits, 5, 9)?;
+        let weight = extract(bits, 45, 15);
+        if method == 4 {
+            text += &format!("3103{:06}", weight);
+        } else if weight < 10000 {
+            text += &format!("3202{:06}", weight);
+        } else {
+            text += &format!("3203{:06}", weight - 10000);
+        }
+        return Some(text);
+    }
+    let method = extract(bits, 1, 5);
+    if method == 12 || method == 13 {
+        if size < 50 + (method - 12) * 10 {
+            return None;
+        }
+        text += &compressed_gtin(bits, 8, 9)?;
+        let last_digit = extract(bits, 48, 2);
+        if method == 12 {
+            text += &format!("392{}", last_digit);
+            decode_general_purpose(bits, 50, &mut text)?;
+        } else {
+            text += &format!("393{}{:03}", last_digit, extract(bits, 50, 10));
+            decode_general_purpose(bits, 60, &mut text)?;
+        }
+        return Some(text);
+    }
+    let method = extract(bits, 1, 7);
+    if !(56..=63).contains(&method) || size != 84 {
+        return None;
+    }
+    let weight_ai = ["310", "320"][method % 2];
+    let date_ai = ["11", "13", "15", "17"][(method - 56) / 2];
+    text += &compressed_gtin(bits, 8, 9)?;
+    let weight = extract(bits, 48, 20);
+    text += &format!("{}{}{:06}", weight_ai, weight / 100000, weight % 100000);
+    let date = extract(bits, 68, 16);
+    // Date value 38400 means that the date is not encoded.
+    if date != 38400 {
+        text += &format!("{}{:02}{:02}{:02}", date_ai, date / 384, date / 32 % 12 + 1, date % 32);
+    }
+    Some(text)
+}
+
+///GTIN with the AI 01. Digits 2-13 are encoded as 4 groups of 3 digits, check digit is calculated.
+fn compressed_gtin(bits: &[bool], pos: usize, first: u8) -> Option<String> {
+    let mut digits = vec![first];
+    for i in 0..4 {
+        let group = extract(bits, pos + i * 10, 10);
+        if group > 999 {
+            return None;
+        }
+        digits.extend_from_slice(&[(group / 100) as u8, (group / 10 % 10) as u8, (group % 10) as u8]);
+    }
+    digits.push(calc_mod10_check(&digits));
+    let mut text = String::from("01");
+    text.extend(digits.iter().map(|d| (b'0' + *d) as char));
+    Some(text)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Encodation {
+    Numeric,
+    Alphanumeric,
+    Iso646,
+}
+
+/**
+Decodes the general purpose data field starting from pos. Numeric, alphanumeric and ISO 646
+encodations are switched with latches, FNC1 returns to the numeric encodation.
+Decoding stops when the remaining bits are padding.
+**/
+fn decode_general_purpose(bits: &[bool], mut pos: usize, text: &mut String) -> Option<()> {
+    let size = bits.len();
+    let mut mode = Encodation::Numeric;
+    while pos < size {
+        let remaining = size - pos;
+        match mode {
+            Encodation::Numeric => {
+                if remaining < 4 {
+                    break;
+                }
+                if extract(bits, pos, 4) == 0 {
+                    mode = Encodation::Alphanumeric;
+                    pos += 4;
+                } else if remaining < 7 {
+                    // Last digit is encoded with 4 bits, value 0 is padding.
+                    let digit = extract(bits, pos, 4);
+                    if digit > 10 {
+                        return None;
+                    }
+                    if digit > 0 {
+                        text.push((b'0' + digit as u8 - 1) as char);
+                    }
+                    break;
+                } else {
+                    let value = extract(bits, pos, 7) - 8;
+                    for digit in [value / 11, value % 11].iter() {
+                        if *digit == 10 {
+                            text.push(GS);
+                        } else {
+                            text.push((b'0' + *digit as u8) as char);
+                        }
+                    }
+                    pos += 7;
+                }
+            },
+            Encodation::Alphanumeric | Encodation::Iso646 => {
+                if remaining < 5 {
+                    if remaining >= 3 && extract(bits, pos, 3) == 0 {
+                        mode = Encodation::Numeric;
+                        pos += 3;
+                        continue;
+                    }
+                    break;
+                }
+                let five = extract(bits, pos, 5);
+                match five {
+                    0..=3 => {
+                        mode = Encodation::Numeric;
+                        pos += 3;
+                    },
+                    4 => {
+                        mode = if mode == Encodation::Alphanumeric {Encodation::Iso646} else {Encodation::Alphanumeric};
+                        pos += 5;
+                    },
+                    5..=14 => {
+                        text.push((b'0' + five as u8 - 5) as char);
+                        pos += 5;
+                    },
+                    15 => {
+                        text.push(GS);
+                        mode = Encodation::Numeric;
+                        pos += 5;
+                    },
+                    _ if mode == Encodation::Alphanumeric => {
+                        if remaining < 6 {
+                            break;
+                        }
+                        let six = extract(bits, pos, 6);
+                        let ch = match six {
+                            32..=57 => (six + 33) as u8,
+                            58..=62 => b"*,-./"[six - 58],
+                            _ => return None,
+                        };
+                        text.push(ch as char);
+                        pos += 6;
+                    },
+                    _ => {
+                        if remaining < 7 {
+                            break;
+                        }
+                        let seven = extract(bits, pos, 7);
+                        if seven < 116 {
+                            let ch = if seven < 90 {seven + 1} else {seven + 7};
+                            text.push(ch as u8 as char);
+                            pos += 7;
+                            continue;
+                        }
+                        if remaining < 8 {
+                            break;
+                        }
+                        let eight = extract(bits, pos, 8);
+                        if !(232..=252).contains(&eight) {
+                            return None;
+                        }
+                        text.push(b"!\"%&'()*+,-./:;<=>?_ "[eight - 232] as char);
+                        pos += 8;
+                    },
+                }
+            },
+        }
+    }
+    while text.ends_with(GS) {
+        text.pop();
+    }
+    Some(())
+}
+
+/**
+Value of the element widths among all the width combinations with the same sum.
+Combinations with elements wider than max_width are not counted, no_narrow leaves out
+the combinations without a single module element.
+Returns None if the widths are not valid for the limits.
+**/
+fn rss_value(widths: &[u8;4], max_width: u8, no_narrow: bool) -> Option<usize> {
+    if widths.iter().any(|w| *w > max_width) || (no_narrow && !widths.contains(&1)) {
+        return None;
+    }
+    let elements = widths.len();
+    let mut n : usize = widths.iter().map(|w| *w as usize).sum();
+    let max_width = max_width as usize;
+    let mut value : isize = 0;
+    let mut narrow_mask = 0;
+    for (bar, width) in widths.iter().enumerate().take(elements - 1) {
+        let mut elm_width = 1;
+        narrow_mask |= 1 << bar;
+        while elm_width < *width as usize {
+            let mut sub_val = combins(n - elm_width - 1, elements - bar - 2) as isize;
+            if no_narrow && narrow_mask == 0 && n - elm_width - (elements - bar - 1) >= elements - bar - 1 {
+                sub_val -= combins(n - elm_width - (elements - bar), elements - bar - 2) as isize;
+            }
+            if elements - bar - 1 > 1 {
+                let mut less_val = 0;
+                let mut mxw_element = n - elm_width - (elements - bar - 2);
+                while mxw_element > max_width {
+                    less_val += combins(n - elm_width - mxw_element - 1, elements - bar - 3);
+                    mxw_element -= 1;
+                }
+                sub_val -= (less_val * (elements - 1 - bar)) as isize;
+            } else if n - elm_width > max_width {
+                sub_val -= 1;
+            }
+            value += sub_val;
+            elm_width += 1;
+            narrow_mask &= !(1 << bar);
+        }
+        n -= elm_width;
+    }
+    if value < 0 {
+        return None;
+    }
+    Some(value as usize)
+}
+
+///Number of combinations of r items from n.
+fn combins(n: usize, r: usize) -> usize {
+    let (min_denom, max_denom) = if n - r > r {(r, n - r)} else {(n - r, r)};
+    let mut value = 1;
+    let mut j = 1;
+    let mut i = n;
+    while i > max_denom {
+        value *= i;
+        if j <= min_denom {
+            value /= j;
+            j += 1;
+        }
+        i -= 1;
+    }
+    while j <= min_denom {
+        value /= j;
+        j += 1;
+    }
+    value
+}
+
+/// Modules of GTIN 00012345678905, "1" is a bar.
+#[cfg(test)]
+pub(crate) const DATABAR_MODULES: &str = "010111111110110101001111111000010111001011011110111001010110000101111111000111010001000011001101";
+
+/// Modules of (01)12345678901231 in Expanded, compressed as numeric data.
+#[cfg(test)]
+pub(crate) const EXPANDED_MODULES: &str = "010111001000111110101111111100001010000011000001010100010111000001101011111100001110001111010011000100001001011100001000111111000010101100100011100001100010000000101010111111110011101";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder_options::DecoderOptions;
+    use crate::test_images::{module_widths, read_texts};
+
+    /// Modules of "AB-C12./" in Expanded, compressed as alphanumeric data.
+    const EXPANDED_ALPHA_MODULES: &str = "010110000111101110101111111100001011000000100001010100010100000110001011110000001110010111011000001100101110000110011000111111000010111111011110101101";
+
+    fn databar_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::DataBar, Symbology::DataBarExpanded])
+    }
+
+    #[test]
+    fn reads_databar() {
+        assert_eq!(read_texts(&module_widths(DATABAR_MODULES), 0.0, &databar_options()), vec!["GS1 DataBar 00012345678905"]);
+    }
+
+    #[test]
+    fn rejects_changed_databar() {
+        // Moves one module from a bar to the next space of the left data character.
+        let modules = DATABAR_MODULES.replacen("0111001011", "0110001011", 1);
+        assert!(read_texts(&module_widths(&modules), 0.0, &databar_options()).is_empty());
+    }
+
+    #[test]
+    fn reads_expanded() {
+        assert_eq!(read_texts(&module_widths(EXPANDED_MODULES), 0.0, &databar_options()), vec!["GS1 DataBar Expanded 0112345678901231"]);
+        assert_eq!(read_texts(&module_widths(EXPANDED_ALPHA_MODULES), 0.0, &databar_options()), vec!["GS1 DataBar Expanded AB-C12./"]);
+    }
+
+    #[test]
+    fn three_characters_are_not_a_symbol() {
+        // Second pair of the two pair symbol has lost its right character.
+        let pairs = [
+            ExpandedPair {finder: 0, forward: true, left: (0, 0), right: Some((5, 17))},
+            ExpandedPair {finder: 0, forward: false, left: (7, 40), right: None},
+        ];
+        assert_eq!(decode_expanded(&pairs), None);
+    }
+}
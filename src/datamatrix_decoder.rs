@@ -0,0 +1,360 @@
+use std::cmp::max;
+use crate::bit_matrix::BitMatrix;
+use crate::bit_reader::BitReader;
+use crate::character_set::decode_bytes;
+use crate::reed_solomon::{correct_errors, GaloisField};
+
+/**
+ECC 200 symbol sizes: rows, columns, data region rows, data region columns,
+data codewords, error correction codewords per block and number of the blocks.
+Blocks are interleaved codeword by codeword, so some of them can have one data codeword less.
+**/
+const DM_SYMBOL_SIZES: [(usize, usize, usize, usize, usize, usize, usize); 30] = [
+    (10, 10, 8, 8, 3, 5, 1), (12, 12, 10, 10, 5, 7, 1), (14, 14, 12, 12, 8, 10, 1),
+    (16, 16, 14, 14, 12, 12, 1), (18, 18, 16, 16, 18, 14, 1), (20, 20, 18, 18, 22, 18, 1),
+    (22, 22, 20, 20, 30, 20, 1), (24, 24, 22, 22, 36, 24, 1), (26, 26, 24, 24, 44, 28, 1),
+    (32, 32, 14, 14, 62, 36, 1), (36, 36, 16, 16, 86, 42, 1), (40, 40, 18, 18, 114, 48, 1),
+    (44, 44, 20, 20, 144, 56, 1), (48, 48, 22, 22, 174, 68, 1), (52, 52, 24, 24, 204, 42, 2),
+    (64, 64, 14, 14, 280, 56, 2), (72, 72, 16, 16, 368, 36, 4), (80, 80, 18, 18, 456, 48, 4),
+    (88, 88, 20, 20, 576, 56, 4), (96, 96, 22, 22, 696, 68, 4), (104, 104, 24, 24, 816, 56, 6),
+    (120, 120, 18, 18, 1050, 68, 6), (132, 132, 20, 20, 1304, 62, 8), (144, 144, 22, 22, 1558, 62, 10),
+    (8, 18, 6, 16, 5, 7, 1), (8, 32, 6, 14, 10, 11, 1), (12, 26, 10, 24, 16, 14, 1),
+    (12, 36, 10, 16, 22, 18, 1), (16, 36, 14, 16, 32, 24, 1), (16, 48, 14, 22, 49, 28, 1),
+];
+
+const C40_SHIFT2_CHARS: &[u8; 27] = b"!\"#$%&'()*+,-./:;<=>?@[\\]^_";
+const TEXT_SHIFT3_CHARS: &[u8; 32] = b"`ABCDEFGHIJKLMNOPQRSTUVWXYZ{|}~\x7f";
+
+/// Group separator that replaces FNC1 in the GS1 data.
+const GS: u8 = 0x1d;
+
+/// Unlatch codeword of C40, Text and X12 modes.
+const UNLATCH: u32 = 254;
+
+/**
+ECC 200 sizes (rows, columns) near the counted size, the closest one first.
+Counting the timing pattern modules of a small or rotated symbol can be off by a few modules.
+**/
+pub fn symbol_sizes_near(rows: usize, cols: usize) -> Vec<(usize,usize)> {
+    let diff = |a: usize, b: usize| (a as i32 - b as i32).unsigned_abs() as usize;
+    let max_diff = |n: usize| max(2, n / 8);
+    let mut sizes: Vec<(usize,usize)> = DM_SYMBOL_SIZES.iter()
+        .filter(|s| diff(s.0, rows) <= max_diff(rows) && diff(s.1, cols) <= max_diff(cols))
+        .map(|s| (s.0, s.1))
+        .collect();
+    sizes.sort_by_key(|s| diff(s.0, rows) + diff(s.1, cols));
+    sizes
+}
+
+/**
+Decodes the sampled Data Matrix modules to text.
+Returns None if the size is not a valid ECC 200 size or there are too many errors.
+Parameters:
+grid - one bit per module with the finder and timing patterns, solid finder sides are left and bottom.
+**/
+pub fn decode_datamatrix(grid: &BitMatrix) -> Option<String> {
+    let &(rows, cols, region_rows, region_cols, data_len, ecc_len, num_blocks) =
+        DM_SYMBOL_SIZES.iter().find(|s| s.0 == grid.height && s.1 == grid.width)?;
+    // Mapping matrix is the symbol without the finder and timing patterns of the data regions.
+    let (regions_v, regions_h) = (rows / (region_rows + 2), cols / (region_cols + 2));
+    let mut mapping = BitMatrix::new(regions_h * region_cols, regions_v * region_rows);
+    for r in 0..mapping.height {
+        for c in 0..mapping.width {
+            let y = r / region_rows * (region_rows + 2) + 1 + r % region_rows;
+            let x = c / region_cols * (region_cols + 2) + 1 + c % region_cols;
+            mapping.set(c, r, grid.get(x, y));
+        }
+    }
+    let positions = codeword_positions(mapping.height, mapping.width);
+    let codewords: Vec<u16> = positions.iter()
+        .map(|cw| cw.iter().fold(0, |acc, (r, c)| acc << 1 | mapping.get(*c, *r) as u16))
+        .collect();
+    if codewords.len() != data_len + ecc_len * num_blocks {
+        return None;
+    }
+    let mut blocks: Vec<Vec<u16>> = vec![Vec::new(); num_blocks];
+    for (k, cw) in codewords.iter().enumerate() {
+        let k = if k < data_len {k} else {k - data_len};
+        blocks[k % num_blocks].push(*cw);
+    }
+    let field = GaloisField::new(0x12d, 256, 1);
+    for block in blocks.iter_mut() {
+        correct_errors(&field, block, ecc_len)?;
+    }
+    let data: Vec<u8> = (0..data_len).map(|k| blocks[k % num_blocks][k / num_blocks] as u8).collect();
+    decode_codewords(&data)
+}
+
+/**
+State of the codeword placement in the mapping matrix.
+    rows, cols - size of the mapping matrix.
+    used - modules that already belong to some codeword.
+    codewords - module positions (row, column) of the placed codewords.
+**/
+struct Placement {
+    rows: i32,
+    cols: i32,
+    used: Vec<bool>,
+    codewords: Vec<[(usize,usize); 8]>,
+}
+
+impl Placement {
+    fn place(&mut self, modules: [(i32,i32); 8]) {
+        let mut codeword = [(0, 0); 8];
+        for (i, (mut r, mut c)) in modules.iter().cloned().enumerate() {
+            if r < 0 {
+                r += self.rows;
+                c += 4 - ((self.rows + 4) % 8);
+            }
+            if c < 0 {
+                c += self.cols;
+                r += 4 - ((self.cols + 4) % 8);
+            }
+            self.used[(r * self.cols + c) as usize] = true;
+            codeword[i] = (r as usize, c as usize);
+        }
+        self.codewords.push(codeword);
+    }
+
+    fn utah(&mut self, r: i32, c: i32) {
+        self.place([(r - 2, c - 2), (r - 2, c - 1), (r - 1, c - 2), (r - 1, c - 1), (r - 1, c), (r, c - 2), (r, c - 1), (r, c)]);
+    }
+
+    fn is_free(&self, r: i32, c: i32) -> bool {
+        !self.used[(r * self.cols + c) as usize]
+    }
+}
+
+/**
+Module positions (row, column) of the codeword bits in the mapping matrix, most significant bit first.
+Codewords are placed in diagonal sweeps of the L-shaped 8 module "utah" figures, the figures that
+do not fit are wrapped to the other side and the corners use special shapes.
+**/
+fn codeword_positions(rows: usize, cols: usize) -> Vec<[(usize,usize); 8]> {
+    let (n, m) = (rows as i32, cols as i32);
+    let mut p = Placement { rows: n, cols: m, used: vec![false; rows * cols], codewords: Vec::new() };
+    let (mut r, mut c) = (4, 0);
+    loop {
+        if r == n && c == 0 {
+            p.place([(n - 1, 0), (n - 1, 1), (n - 1, 2), (0, m - 2), (0, m - 1), (1, m - 1), (2, m - 1), (3, m - 1)]);
+        }
+        if r == n - 2 && c == 0 && m % 4 != 0 {
+            p.place([(n - 3, 0), (n - 2, 0), (n - 1, 0), (0, m - 4), (0, m - 3), (0, m - 2), (0, m - 1), (1, m - 1)]);
+        }
+        if r == n - 2 && c == 0 && m % 8 == 4 {
+            p.place([(n - 3, 0), (n - 2, 0), (n - 1, 0), (0, m - 2), (0, m - 1), (1, m - 1), (2, m - 1), (3, m - 1)]);
+        }
+        if r == n + 4 && c == 2 && m % 8 == 0 {
+            p.place([(n - 1, 0), (n - 1, m - 1), (0, m - 3), (0, m - 2), (0, m - 1), (1, m - 3), (1, m - 2), (1, m - 1)]);
+        }
+        // Sweep up and right.
+        loop {
+            if r < n && c >= 0 && p.is_free(r, c) {
+                p.utah(r, c);
+            }
+            r -= 2;
+            c += 2;
+            if r < 0 || c >= m {
+                break;
+            }
+        }
+        r += 1;
+        c += 3;
+        // Sweep down and left.
+        loop {
+            if r >= 0 && c < m && p.is_free(r, c) {
+                p.utah(r, c);
+            }
+            r += 2;
+            c -= 2;
+            if r >= n || c < 0 {
+                break;
+            }
+        }
+        r += 3;
+        c += 1;
+        if r >= n && c >= m {
+            break;
+        }
+    }
+    p.codewords
+}
+
+/**
+Decodes the data codewords. ASCII is the starting mode, the other modes are latched to
+and return to ASCII by an unlatch codeword or at the end of the symbol.
+FNC1 in the first position marks GS1 data, its other FNC1 characters are returned as GS.
+**/
+fn decode_codewords(data: &[u8]) -> Option<String> {
+    let mut reader = BitReader::new(data);
+    let mut text = String::new();
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut eci = None;
+    let mut trailer = "";
+    let mut upper_shift = false;
+    while reader.available() >= 8 {
+        let first = reader.pos == 0;
+        let codeword = reader.read(8)?;
+        match codeword {
+            1..=128 => {
+                bytes.push((codeword - 1) as u8 + if upper_shift {128} else {0});
+                upper_shift = false;
+            }
+            // Padding.
+            129 => break,
+            130..=229 => bytes.extend(format!("{:02}", codeword - 130).bytes()),
+            230 => decode_c40_text(&mut reader, &mut bytes, false)?,
+            231 => decode_base256(&mut reader, &mut bytes)?,
+            232 => {
+                if !first {
+                    bytes.push(GS);
+                }
+            }
+            // Structured append, symbol sequence and file identification are not needed for the text.
+            233 => {
+                reader.read(24)?;
+            }
+            // Reader programming.
+            234 => {}
+            235 => upper_shift = true,
+            236 | 237 if first => {
+                bytes.extend(if codeword == 236 {"[)>\u{1e}05\u{1d}"} else {"[)>\u{1e}06\u{1d}"}.bytes());
+                trailer = "\u{1e}\u{4}";
+            }
+            238 => decode_x12(&mut reader, &mut bytes)?,
+            239 => decode_c40_text(&mut reader, &mut bytes, true)?,
+            240 => decode_edifact(&mut reader, &mut bytes)?,
+            241 => {
+                text.push_str(&decode_bytes(&bytes, eci)?);
+                bytes.clear();
+                eci = Some(read_eci(&mut reader)?);
+            }
+            _ => return None,
+        }
+    }
+    text.push_str(&decode_bytes(&bytes, eci)?);
+    text.push_str(trailer);
+    Some(text)
+}
+
+///Three values 0-39 packed to two codewords.
+fn read_triplet(reader: &mut BitReader) -> Option<[u32; 3]> {
+    // One codeword left at the end of the symbol is ASCII encoded.
+    if reader.available() < 16 {
+        return None;
+    }
+    let first = reader.read(8)?;
+    if first == UNLATCH {
+        return None;
+    }
+    let value = (first << 8 | reader.read(8)?).checked_sub(1)?;
+    Some([value / 1600, value / 40 % 40, value % 40])
+}
+
+/**
+C40 has upper case letters and Text lower case letters in the basic set,
+the other characters are encoded with the shift values 0-2.
+**/
+fn decode_c40_text(reader: &mut BitReader, bytes: &mut Vec<u8>, text_mode: bool) -> Option<()> {
+    let mut shift = 0;
+    let mut upper_shift = false;
+    while let Some(values) = read_triplet(reader) {
+        for value in values.iter().map(|v| *v as u8) {
+            let ch = match (shift, value) {
+                (0, 0..=2) => {
+                    shift = value + 1;
+                    continue;
+                }
+                (0, 3) => b' ',
+                (0, 4..=13) => b'0' + value - 4,
+                (0, 14..=39) => (if text_mode {b'a'} else {b'A'}) + value - 14,
+                (1, 0..=31) => value,
+                (2, 0..=26) => C40_SHIFT2_CHARS[value as usize],
+                (2, 27) => GS,
+                (2, 30) => {
+                    shift = 0;
+                    upper_shift = true;
+                    continue;
+                }
+                (3, 0..=31) if text_mode => TEXT_SHIFT3_CHARS[value as usize],
+                (3, 0..=31) => value + 96,
+                _ => return None,
+            };
+            shift = 0;
+            bytes.push(ch + if upper_shift {128} else {0});
+            upper_shift = false;
+        }
+    }
+    Some(())
+}
+
+fn decode_x12(reader: &mut BitReader, bytes: &mut Vec<u8>) -> Option<()> {
+    while let Some(values) = read_triplet(reader) {
+        for value in values.iter().map(|v| *v as u8) {
+            bytes.push(match value {
+                0 => b'\r',
+                1 => b'*',
+                2 => b'>',
+                3 => b' ',
+                4..=13 => b'0' + value - 4,
+                14..=39 => b'A' + value - 14,
+                _ => return None,
+            });
+        }
+    }
+    Some(())
+}
+
+///Four 6 bit values in three codewords, the value 0x1f unlatches at the next codeword boundary.
+fn decode_edifact(reader: &mut BitReader, bytes: &mut Vec<u8>) -> Option<()> {
+    while reader.available() > 16 {
+        for _ in 0..4 {
+            let value = reader.read(6)? as u8;
+            if value == 0x1f {
+                let rest = (8 - reader.pos % 8) % 8;
+                reader.read(rest)?;
+                return Some(());
+            }
+            bytes.push(if value & 0x20 == 0 {value | 0x40} else {value});
+        }
+    }
+    Some(())
+}
+
+/**
+Base 256 field length and bytes are randomized with their codeword positions.
+Length 0 means that the field continues to the end of the symbol.
+**/
+fn decode_base256(reader: &mut BitReader, bytes: &mut Vec<u8>) -> Option<()> {
+    let mut position = reader.pos / 8 + 1;
+    let mut next = |reader: &mut BitReader| -> Option<usize> {
+        let random = (149 * position) % 255 + 1;
+        position += 1;
+        Some((reader.read(8)? as usize + 256 - random) % 256)
+    };
+    let length = next(reader)?;
+    let count = match length {
+        0 => reader.available() / 8,
+        1..=249 => length,
+        _ => 250 * (length - 249) + next(reader)?,
+    };
+    for _ in 0..count {
+        bytes.push(next(reader)? as u8);
+    }
+    Some(())
+}
+
+///ECI assignment number, 1 to 3 codewords long depending on the value of the first one.
+fn read_eci(reader: &mut BitReader) -> Option<u32> {
+    let first = reader.read(8)?;
+    match first {
+        1..=127 => Some(first - 1),
+        128..=191 => Some((first - 128) * 254 + 127 + reader.read(8)?.checked_sub(1)?),
+        192..=253 => {
+            let second = reader.read(8)?.checked_sub(1)?;
+            let third = reader.read(8)?.checked_sub(1)?;
+            Some((first - 192) * 64516 + 16383 + second * 254 + third)
+        }
+        _ => None,
+    }
+}
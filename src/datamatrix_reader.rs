@@ -0,0 +1,252 @@
+use crate::bit_matrix::BitMatrix;
+use crate::dark_area::{cross, distance, fill_dark_area, hull_quadrilateral, is_dark, lerp, Point};
+use crate::datamatrix_decoder::{decode_datamatrix, symbol_sizes_near};
+use crate::grid_sampler::{sample_grid, PerspectiveTransform};
+use crate::symbology::{Barcode, Symbology};
+
+/// Dark areas that are smaller than this many pixels in width or height are not tried as symbols.
+const DM_MIN_SIDE: usize = 16;
+
+/// Number of the points the solid finder sides are checked from.
+const SIDE_SAMPLES: usize = 20;
+
+/// Share of the side samples that has to be dark.
+const MIN_SIDE_DARK: f32 = 0.8;
+
+/// Distance in pixels from the edge of the area to the points where the finder sides are sampled.
+const SIDE_INSET: f32 = 1.2;
+
+/**
+Finds and decodes Data Matrix symbols from the binarized image.
+Every connected dark area is a candidate. Its convex hull gives the corners of the
+solid L-shaped finder, the timing patterns on the two other sides give the number of the modules.
+Parameters:
+image - binarized image.
+**/
+pub fn find_datamatrix_codes(image: &BitMatrix) -> Vec<Barcode> {
+    let mut found = Vec::new();
+    let mut visited = BitMatrix::new(image.width, image.height);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            if !image.get(x, y) || visited.get(x, y) {
+                continue;
+            }
+            let area = fill_dark_area(image, &mut visited, x, y);
+            if area.width < DM_MIN_SIDE || area.height < DM_MIN_SIDE {
+                continue;
+            }
+            if let Some(text) = decode_area(image, &area.hull) {
+                found.push(Barcode::new(Symbology::DataMatrix, text));
+            }
+        }
+    }
+    found
+}
+
+/**
+True if the side from the corner to the end is dark along its whole length.
+Samples are taken a bit inside the area, towards the other side of the corner.
+**/
+fn is_solid_side(image: &BitMatrix, corner: Point, end: Point, other: Point) -> bool {
+    let inward = inward_normal(corner, end, other);
+    let dark = (0..SIDE_SAMPLES).filter(|i| {
+        let t = 0.1 + 0.8 * *i as f32 / (SIDE_SAMPLES - 1) as f32;
+        let p = lerp(corner, end, t);
+        is_dark(image, (p.0 + inward.0 * SIDE_INSET, p.1 + inward.1 * SIDE_INSET))
+    }).count();
+    dark as f32 >= SIDE_SAMPLES as f32 * MIN_SIDE_DARK
+}
+
+///Unit vector perpendicular to the side from a to b, pointing to the same side as the point c.
+fn inward_normal(a: Point, b: Point, c: Point) -> Point {
+    let len = distance(a, b).max(1.0);
+    let (nx, ny) = (-(b.1 - a.1) / len, (b.0 - a.0) / len);
+    if nx * (c.0 - a.0) + ny * (c.1 - a.1) < 0.0 {(-nx, -ny)} else {(nx, ny)}
+}
+
+/**
+Width of the solid finder side measured perpendicular to it, the lower quartile
+of the dark runs so that the data modules next to the finder are not counted.
+**/
+fn finder_thickness(image: &BitMatrix, corner: Point, end: Point, other: Point) -> f32 {
+    let inward = inward_normal(corner, end, other);
+    let limit = distance(corner, other) / 4.0;
+    let mut runs: Vec<f32> = (0..SIDE_SAMPLES).map(|i| {
+        let t = 0.1 + 0.8 * i as f32 / (SIDE_SAMPLES - 1) as f32;
+        let p = lerp(corner, end, t);
+        let at = |d: f32| is_dark(image, (p.0 + inward.0 * d, p.1 + inward.1 * d));
+        // Hull line of a rotated symbol touches only the outermost pixels of the side.
+        let mut start = 0.25;
+        while start < 2.0 * SIDE_INSET && !at(start) {
+            start += 0.5;
+        }
+        let mut d = start;
+        while d < limit && at(d) {
+            d += 0.5;
+        }
+        d - start
+    }).collect();
+    runs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    runs[SIDE_SAMPLES / 4]
+}
+
+/**
+Number of the modules in the timing pattern from a to b. The thickness of the finder can include
+the dark data modules next to it, so the lines at different distances are tried and the one with
+the most dark runs is taken as the timing pattern.
+**/
+fn count_timing_modules(image: &BitMatrix, a: Point, b: Point, inward: Point, thickness: f32) -> usize {
+    let mut best = 0;
+    // Jagged module edges of a rotated symbol add runs next to the edge of the area.
+    let mut d = thickness * 0.3;
+    while d < thickness * 0.8 {
+        let runs = count_dark_runs(image, (a.0 + inward.0 * d, a.1 + inward.1 * d), (b.0 + inward.0 * d, b.1 + inward.1 * d), thickness * 0.35);
+        best = best.max(runs);
+        d += 0.5;
+    }
+    2 * best
+}
+
+/**
+Number of the dark runs on the line from a to b. Color changes shorter than the minimum run
+are the jagged edges of the modules and are ignored.
+**/
+fn count_dark_runs(image: &BitMatrix, a: Point, b: Point, min_run: f32) -> usize {
+    let steps = (distance(a, b) * 2.0) as usize;
+    let min_samples = ((min_run * 2.0) as usize).max(1);
+    let mut runs = 0;
+    let mut color = false;
+    let mut changed = 0;
+    for i in 0..=steps {
+        let dark = is_dark(image, lerp(a, b, i as f32 / steps.max(1) as f32));
+        changed = if dark != color {changed + 1} else {0};
+        if changed >= min_samples {
+            color = dark;
+            changed = 0;
+            if dark {
+                runs += 1;
+            }
+        }
+    }
+    runs
+}
+
+/**
+Tries every corner of the hull quadrilateral that has two solid sides as the corner of the finder.
+Parameters:
+image - binarized image.
+hull - convex hull of the dark area.
+**/
+fn decode_area(image: &BitMatrix, hull: &[Point]) -> Option<String> {
+    let quad = hull_quadrilateral(hull)?;
+    for i in 0..4 {
+        let corner = quad[i];
+        let (a, b) = (quad[(i + 3) % 4], quad[(i + 1) % 4]);
+        if !is_solid_side(image, corner, a, b) || !is_solid_side(image, corner, b, a) {
+            continue;
+        }
+        // Finder is on the left and bottom sides of the upright symbol.
+        let (top_left, bottom_right) = if cross(corner, b, a) < 0.0 {(a, b)} else {(b, a)};
+        if let Some(text) = decode_symbol(image, corner, top_left, bottom_right) {
+            return Some(text);
+        }
+    }
+    None
+}
+
+/**
+Counts the modules from the timing patterns and samples the symbol.
+Parameters:
+corner - outer corner of the L-shaped finder.
+top_left, bottom_right - ends of the finder sides.
+**/
+fn decode_symbol(image: &BitMatrix, corner: Point, top_left: Point, bottom_right: Point) -> Option<String> {
+    let module_w = finder_thickness(image, corner, top_left, bottom_right);
+    let module_h = finder_thickness(image, corner, bottom_right, top_left);
+    if module_w < 1.0 || module_h < 1.0 {
+        return None;
+    }
+    let top_right = (top_left.0 + bottom_right.0 - corner.0, top_left.1 + bottom_right.1 - corner.1);
+    let down = inward_normal(top_left, top_right, corner);
+    let left = inward_normal(bottom_right, top_right, corner);
+    let cols = count_timing_modules(image, top_left, top_right, down, module_h);
+    let rows = count_timing_modules(image, bottom_right, top_right, left, module_w);
+    for (rows, cols) in symbol_sizes_near(rows, cols) {
+        let top_right = refine_top_right(image, corner, top_left, bottom_right, top_right, rows, cols);
+        let src = [(0.0, 0.0), (cols as f32, 0.0), (cols as f32, rows as f32), (0.0, rows as f32)];
+        let transform = PerspectiveTransform::quadrilateral_to_quadrilateral(&src, &[top_left, top_right, bottom_right, corner]);
+        if let Some(text) = sample_grid(image, cols, rows, &transform).and_then(|grid| decode_datamatrix(&grid)) {
+            return Some(text);
+        }
+    }
+    None
+}
+
+/**
+Parallelogram estimate of the corner between the timing patterns is wrong for the perspective,
+nearby points are tried and the one that gives the most correct timing modules is returned.
+**/
+fn refine_top_right(image: &BitMatrix, corner: Point, top_left: Point, bottom_right: Point, estimate: Point, rows: usize, cols: usize) -> Point {
+    let module = (distance(top_left, estimate) / cols as f32, distance(bottom_right, estimate) / rows as f32);
+    let src = [(0.0, 0.0), (cols as f32, 0.0), (cols as f32, rows as f32), (0.0, rows as f32)];
+    let mut best = (0, estimate);
+    for dy in -6..=6 {
+        for dx in -6..=6 {
+            let candidate = (estimate.0 + dx as f32 * module.0 / 4.0, estimate.1 + dy as f32 * module.1 / 4.0);
+            let transform = PerspectiveTransform::quadrilateral_to_quadrilateral(&src, &[top_left, candidate, bottom_right, corner]);
+            let module_at = |x: usize, y: usize| {
+                let p = transform.transform(x as f32 + 0.5, y as f32 + 0.5);
+                is_dark(image, p)
+            };
+            let top = (0..cols).filter(|x| module_at(*x, 0) == (x % 2 == 0)).count();
+            let right = (0..rows).filter(|y| module_at(cols - 1, *y) == (y % 2 == 1)).count();
+            if top + right > best.0 {
+                best = (top + right, candidate);
+            }
+        }
+    }
+    best.1
+}
+
+/// 18 X 18 symbol of "Hello World 123" in ASCII encodation, "1" is a dark module.
+#[cfg(test)]
+pub(crate) const ASCII_MODULES: [&str; 18] = [
+    "101010101010101010", "101101101101000001", "110001101001110010", "111011000110101011", "110000010010101000",
+    "111000100100001001", "110011000010101100", "111011010110110001", "100101001001110000", "101101110100010111",
+    "111011101111101010", "101110011011101011", "101100000101001110", "101101111101111001", "111100001011001110",
+    "101011100100000011", "101101011011101010", "111111111111111111",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::ASCII_MODULES;
+    use crate::decoder_options::DecoderOptions;
+    use crate::symbology::Symbology;
+    use crate::test_images::read_modules;
+
+    /// 14 X 14 symbol of "ABCDEF" in C40 and "xy" in ASCII encodation.
+    const C40_MODULES: [&str; 14] = [
+        "10101010101010", "10100110101111", "10110010110000", "10011000111011", "10111000001100", "10111001000101",
+        "11110000100000", "11111110101111", "10100011111100", "11011100010111", "10010101010100", "10101111100111",
+        "10110101010100", "11111111111111",
+    ];
+
+    fn datamatrix_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::DataMatrix])
+    }
+
+    #[test]
+    fn reads_data_matrix() {
+        assert_eq!(read_modules(&ASCII_MODULES, 0.0, &datamatrix_options()), vec!["Data Matrix Hello World 123"]);
+        assert_eq!(read_modules(&C40_MODULES, 0.0, &datamatrix_options()), vec!["Data Matrix ABCDEFxy"]);
+    }
+
+    #[test]
+    fn corrects_errors() {
+        let mut rows : Vec<String> = ASCII_MODULES.iter().map(|r| r.to_string()).collect();
+        // Three data modules in the middle are flipped.
+        rows[8].replace_range(7..10, "011");
+        let rows : Vec<&str> = rows.iter().map(|r| r.as_str()).collect();
+        assert_eq!(read_modules(&rows, 0.0, &datamatrix_options()), vec!["Data Matrix Hello World 123"]);
+    }
+}
@@ -0,0 +1,267 @@
+use std::cmp::max;
+use crate::symbology::Symbology;
+
+/// Slice size above which the image is handled as big, the crossings then get a wider buffer.
+const BIG_IMAGE_SLICE_SIZE: usize = 40;
+
+/// Part of the first guard bar width that the other guard bars may differ from it.
+const WIDTH_TOLERANCE: f32 = 0.12;
+
+/// Part of the local contrast that the crossing threshold is widened by on both sides.
+const THRESHOLD_BUFFER: f32 = 0.04;
+
+/// Slices with smaller difference between the lightest and the darkest pixel have no bars.
+const CONTRAST_FLOOR: u8 = 16;
+
+/// Angle between the scan lines in degrees, the lines cover the half circle.
+const SCAN_ANGLE_STEP: u16 = 15;
+
+/// Angle between the scan lines in the try harder mode.
+const TRY_HARDER_ANGLE_STEP: u16 = 5;
+
+/**
+Gray values that the barcodes are detected from.
+    Channel - one color channel of the image, as it is.
+    Bt601, Bt709 - luma mixed from the red, green and blue channels with the BT.601 or BT.709 weights.
+    MaxChannel, MinChannel - the lightest or the darkest of the red, green and blue channels.
+Images with less than 3 channels are gray, their first channel is used for all the mixes.
+**/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Luminance {
+    Channel(usize),
+    Bt601,
+    Bt709,
+    MaxChannel,
+    MinChannel,
+}
+
+/**
+Settings of the barcode detection. Default values suit the usual photos, they are changed with the builder methods:
+    DecoderOptions::default().row_step(2).symbologies(&[Symbology::Ean13]).max_results(1)
+row_step - distance between the scan lines in pixels, by default log10 of the image height (or width for the columns) * 6.
+slice_size - number of the pixels in one slice of the scan line, by default max(30, max(w, h) / 40).
+big_image_slice_size - slices bigger than this make the image a big one, its crossings have wider buffers. Default 40.
+width_tolerance - part of the guard bar width the other guard bars may differ from it. Default 0.12.
+threshold_buffer - part of the local contrast that widens the crossing threshold. Default 0.04.
+contrast_floor - slices with smaller pixel value range have no bars. Default 16.
+symbologies - symbologies that are searched for, the linear ones by default. 2D symbols are searched for from the whole image,
+    which is slower, only if they are listed. Symbology::ALL has all of them.
+luminance - gray values the barcodes are read from, BT.601 luma by default.
+try_channels - if nothing is found, the red, green and blue channels are tried in turn. Off by default.
+scan_angles - angles of the scan lines in degrees, 0 are the rows and 90 the columns. Every line is read in both directions,
+    so the angles from 0 to 179 cover all orientations. By default the rows, the columns and the lines at every 15 degrees.
+    For example scan_angles(&[0]) reads only the rows, as the fastest setting.
+code39_full_ascii - decodes the Full ASCII shift pairs of Code 39, the codes with them are Code 39 Extended. Off by default,
+    the pairs are valid Code 39 data too.
+code39_check_digit - the last Code 39 character is the mod 43 check character, it is validated and removed. Off by default.
+itf_check_digit - the mod 10 check digit is validated also for the ITF codes that are not 14 digits long. Off by default.
+codabar_start_stop - the Codabar start and stop characters (A-D) are included in the text. Off by default.
+codabar_check_char - the last Codabar data character is the mod 16 check character, it is validated and removed. Off by default.
+try_harder - halves the distance between the scan lines and scans the rotated lines at every 5 degrees instead of 15,
+    unless the angles are set.
+min_confidence - codes with a smaller confidence are left out. Default 0.0 returns all codes, 0.75 returns the linear codes
+    only if they were read from at least two lines.
+max_results - number of the barcodes returned at most, no limit by default.
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecoderOptions {
+    pub(crate) row_step: Option<u32>,
+    pub(crate) slice_size: Option<usize>,
+    pub(crate) big_image_slice_size: usize,
+    pub(crate) width_tolerance: f32,
+    pub(crate) threshold_buffer: f32,
+    pub(crate) contrast_floor: u8,
+    pub(crate) symbologies: Option<Vec<Symbology>>,
+    pub(crate) luminance: Luminance,
+    pub(crate) try_channels: bool,
+    pub(crate) scan_angles: Option<Vec<u16>>,
+    pub(crate) code39_full_ascii: bool,
+    pub(crate) code39_check_digit: bool,
+    pub(crate) itf_check_digit: bool,
+    pub(crate) codabar_start_stop: bool,
+    pub(crate) codabar_check_char: bool,
+    pub(crate) try_harder: bool,
+    pub(crate) min_confidence: f32,
+    pub(crate) max_results: Option<usize>,
+}
+
+impl Default for DecoderOptions {
+    fn default() -> DecoderOptions {
+        DecoderOptions {
+            row_step: None,
+            slice_size: None,
+            big_image_slice_size: BIG_IMAGE_SLICE_SIZE,
+            width_tolerance: WIDTH_TOLERANCE,
+            threshold_buffer: THRESHOLD_BUFFER,
+            contrast_floor: CONTRAST_FLOOR,
+            symbologies: None,
+            luminance: Luminance::Bt601,
+            try_channels: false,
+            scan_angles: None,
+            code39_full_ascii: false,
+            code39_check_digit: false,
+            itf_check_digit: false,
+            codabar_start_stop: false,
+            codabar_check_char: false,
+            try_harder: false,
+            min_confidence: 0.0,
+            max_results: None,
+        }
+    }
+}
+
+impl DecoderOptions {
+    pub fn row_step(mut self, step: u32) -> DecoderOptions {
+        self.row_step = Some(step);
+        self
+    }
+
+    pub fn slice_size(mut self, size: usize) -> DecoderOptions {
+        self.slice_size = Some(size);
+        self
+    }
+
+    pub fn big_image_slice_size(mut self, size: usize) -> DecoderOptions {
+        self.big_image_slice_size = size;
+        self
+    }
+
+    pub fn width_tolerance(mut self, tolerance: f32) -> DecoderOptions {
+        self.width_tolerance = tolerance;
+        self
+    }
+
+    pub fn threshold_buffer(mut self, buffer: f32) -> DecoderOptions {
+        self.threshold_buffer = buffer;
+        self
+    }
+
+    pub fn contrast_floor(mut self, floor: u8) -> DecoderOptions {
+        self.contrast_floor = floor;
+        self
+    }
+
+    pub fn symbologies(mut self, symbologies: &[Symbology]) -> DecoderOptions {
+        self.symbologies = Some(symbologies.to_vec());
+        self
+    }
+
+    pub fn luminance(mut self, luminance: Luminance) -> DecoderOptions {
+        self.luminance = luminance;
+        self
+    }
+
+    pub fn try_channels(mut self, try_channels: bool) -> DecoderOptions {
+        self.try_channels = try_channels;
+        self
+    }
+
+    pub fn scan_angles(mut self, angles: &[u16]) -> DecoderOptions {
+        self.scan_angles = Some(angles.to_vec());
+        self
+    }
+
+    pub fn code39_full_ascii(mut self, full_ascii: bool) -> DecoderOptions {
+        self.code39_full_ascii = full_ascii;
+        self
+    }
+
+    pub fn code39_check_digit(mut self, check_digit: bool) -> DecoderOptions {
+        self.code39_check_digit = check_digit;
+        self
+    }
+
+    pub fn itf_check_digit(mut self, check_digit: bool) -> DecoderOptions {
+        self.itf_check_digit = check_digit;
+        self
+    }
+
+    pub fn codabar_start_stop(mut self, start_stop: bool) -> DecoderOptions {
+        self.codabar_start_stop = start_stop;
+        self
+    }
+
+    pub fn codabar_check_char(mut self, check_char: bool) -> DecoderOptions {
+        self.codabar_check_char = check_char;
+        self
+    }
+
+    pub fn try_harder(mut self, try_harder: bool) -> DecoderOptions {
+        self.try_harder = try_harder;
+        self
+    }
+
+    pub fn min_confidence(mut self, confidence: f32) -> DecoderOptions {
+        self.min_confidence = confidence;
+        self
+    }
+
+    pub fn max_results(mut self, max_results: usize) -> DecoderOptions {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    ///True if the symbology is searched for.
+    pub fn is_enabled(&self, symbology: Symbology) -> bool {
+        match &self.symbologies {
+            Some(symbologies) => symbologies.contains(&symbology),
+            None => symbology.is_linear(),
+        }
+    }
+
+    ///True if any of the symbologies is searched for.
+    pub(crate) fn any_enabled(&self, symbologies: &[Symbology]) -> bool {
+        symbologies.iter().any(|s| self.is_enabled(*s))
+    }
+
+    ///Distance between the scan lines across the image side of the given length.
+    pub(crate) fn line_step(&self, len: u32) -> u32 {
+        let step = self.row_step.unwrap_or_else(|| ((len as f64).log10() * 6.0) as u32);
+        if self.try_harder {
+            max(1, step / 2)
+        } else {
+            max(1, step)
+        }
+    }
+
+    pub(crate) fn line_slice_size(&self, dim: (u32,u32)) -> usize {
+        max(1, self.slice_size.unwrap_or_else(|| max(30, max(dim.0, dim.1) / 40) as usize))
+    }
+
+    ///Angles of the scan lines from 0 to 179 degrees, the rows and the columns first.
+    pub(crate) fn line_angles(&self) -> Vec<u16> {
+        let mut angles : Vec<u16> = match &self.scan_angles {
+            Some(angles) => angles.iter().map(|deg| deg % 180).collect(),
+            None => {
+                let step = if self.try_harder {TRY_HARDER_ANGLE_STEP} else {SCAN_ANGLE_STEP};
+                (0..180).step_by(step as usize).collect()
+            },
+        };
+        angles.sort_by_key(|deg| (deg % 90 != 0, *deg));
+        angles.dedup();
+        angles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_and_columns_are_scanned_first() {
+        let angles = DecoderOptions::default().line_angles();
+        assert_eq!(angles[..4], [0, 90, 15, 30]);
+        assert_eq!(angles.len(), 12);
+        assert_eq!(DecoderOptions::default().try_harder(true).line_angles().len(), 36);
+        assert_eq!(DecoderOptions::default().scan_angles(&[200, 90, 20, 180]).line_angles(), vec![0, 90, 20]);
+    }
+
+    #[test]
+    fn symbols_2d_are_searched_for_if_listed() {
+        let options = DecoderOptions::default();
+        assert!(options.is_enabled(Symbology::Ean13) && options.is_enabled(Symbology::DataBarExpanded));
+        assert!(!options.any_enabled(&[Symbology::QrCode, Symbology::DataMatrix, Symbology::Aztec]));
+        let options = options.symbologies(&Symbology::ALL);
+        assert!(Symbology::ALL.iter().all(|s| options.is_enabled(*s)));
+    }
+}
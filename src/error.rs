@@ -0,0 +1,56 @@
+use std::fmt;
+use std::io;
+
+///Errors of the barcode detection.
+#[derive(Debug)]
+pub enum Error {
+    ///Image file could not be read.
+    Io(io::Error),
+    ///Image format is not supported or the image data is broken.
+    UnsupportedImage(String),
+    ///Image is smaller than any barcode, the width and the height of the image.
+    ImageTooSmall(u32, u32),
+    ///Color channel is not in the image, the channel and the number of the channels.
+    InvalidChannel(usize, usize),
+    ///Raw pixel buffer does not match its layout, the reason.
+    InvalidBuffer(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "could not read the image: {}", e),
+            Error::UnsupportedImage(e) => write!(f, "unsupported image: {}", e),
+            Error::ImageTooSmall(w, h) => write!(f, "image {} X {} is too small", w, h),
+            Error::InvalidChannel(channel, count) => write!(f, "color channel {} is not in the image with {} channels", channel, count),
+            Error::InvalidBuffer(e) => write!(f, "invalid pixel buffer: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Error {
+        match e {
+            image::ImageError::IoError(e) => Error::Io(e),
+            e => Error::UnsupportedImage(e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::bit_matrix::BitMatrix;
+
+/**
+Projective transform between two planes, maps (x, y) to
+((a11*x + a21*y + a31) / d, (a12*x + a22*y + a32) / d) where d = a13*x + a23*y + a33.
+**/
+#[derive(Clone, Copy, Debug)]
+pub struct PerspectiveTransform {
+    a11: f32, a21: f32, a31: f32,
+    a12: f32, a22: f32, a32: f32,
+    a13: f32, a23: f32, a33: f32,
+}
+
+impl PerspectiveTransform {
+    /**
+    Transform that maps the source quadrilateral to the destination quadrilateral.
+    Corners are in the order top left, top right, bottom right and bottom left.
+    **/
+    pub fn quadrilateral_to_quadrilateral(src: &[(f32,f32);4], dst: &[(f32,f32);4]) -> PerspectiveTransform {
+        let to_square = PerspectiveTransform::square_to_quadrilateral(src).adjoint();
+        PerspectiveTransform::square_to_quadrilateral(dst).times(&to_square)
+    }
+
+    fn square_to_quadrilateral(q: &[(f32,f32);4]) -> PerspectiveTransform {
+        let ((x0, y0), (x1, y1), (x2, y2), (x3, y3)) = (q[0], q[1], q[2], q[3]);
+        let dx3 = x0 - x1 + x2 - x3;
+        let dy3 = y0 - y1 + y2 - y3;
+        if dx3 == 0.0 && dy3 == 0.0 {
+            // Parallelogram, the transform is affine.
+            return PerspectiveTransform {
+                a11: x1 - x0, a21: x2 - x1, a31: x0,
+                a12: y1 - y0, a22: y2 - y1, a32: y0,
+                a13: 0.0, a23: 0.0, a33: 1.0,
+            };
+        }
+        let dx1 = x1 - x2;
+        let dx2 = x3 - x2;
+        let dy1 = y1 - y2;
+        let dy2 = y3 - y2;
+        let denominator = dx1 * dy2 - dx2 * dy1;
+        let a13 = (dx3 * dy2 - dx2 * dy3) / denominator;
+        let a23 = (dx1 * dy3 - dx3 * dy1) / denominator;
+        PerspectiveTransform {
+            a11: x1 - x0 + a13 * x1, a21: x3 - x0 + a23 * x3, a31: x0,
+            a12: y1 - y0 + a13 * y1, a22: y3 - y0 + a23 * y3, a32: y0,
+            a13, a23, a33: 1.0,
+        }
+    }
+
+    ///Adjoint matrix works as the inverse, the transform does not depend on the scale.
+    fn adjoint(&self) -> PerspectiveTransform {
+        PerspectiveTransform {
+            a11: self.a22 * self.a33 - self.a23 * self.a32,
+            a21: self.a23 * self.a31 - self.a21 * self.a33,
+            a31: self.a21 * self.a32 - self.a22 * self.a31,
+            a12: self.a13 * self.a32 - self.a12 * self.a33,
+            a22: self.a11 * self.a33 - self.a13 * self.a31,
+            a32: self.a12 * self.a31 - self.a11 * self.a32,
+            a13: self.a12 * self.a23 - self.a13 * self.a22,
+            a23: self.a13 * self.a21 - self.a11 * self.a23,
+            a33: self.a11 * self.a22 - self.a12 * self.a21,
+        }
+    }
+
+    fn times(&self, o: &PerspectiveTransform) -> PerspectiveTransform {
+        PerspectiveTransform {
+            a11: self.a11 * o.a11 + self.a21 * o.a12 + self.a31 * o.a13,
+            a21: self.a11 * o.a21 + self.a21 * o.a22 + self.a31 * o.a23,
+            a31: self.a11 * o.a31 + self.a21 * o.a32 + self.a31 * o.a33,
+            a12: self.a12 * o.a11 + self.a22 * o.a12 + self.a32 * o.a13,
+            a22: self.a12 * o.a21 + self.a22 * o.a22 + self.a32 * o.a23,
+            a32: self.a12 * o.a31 + self.a22 * o.a32 + self.a32 * o.a33,
+            a13: self.a13 * o.a11 + self.a23 * o.a12 + self.a33 * o.a13,
+            a23: self.a13 * o.a21 + self.a23 * o.a22 + self.a33 * o.a23,
+            a33: self.a13 * o.a31 + self.a23 * o.a32 + self.a33 * o.a33,
+        }
+    }
+
+    pub fn transform(&self, x: f32, y: f32) -> (f32,f32) {
+        let d = self.a13 * x + self.a23 * y + self.a33;
+        ((self.a11 * x + self.a21 * y + self.a31) / d, (self.a12 * x + self.a22 * y + self.a32) / d)
+    }
+}
+
+/**
+Reads the modules of the symbol from the centers of the grid cells.
+Points that are less than one pixel outside of the image are moved to the border,
+otherwise None is returned.
+Parameters:
+image - binarized image.
+width, height - number of the modules.
+transform - maps module coordinates to image coordinates.
+**/
+pub fn sample_grid(image: &BitMatrix, width: usize, height: usize, transform: &PerspectiveTransform) -> Option<BitMatrix> {
+    let mut grid = BitMatrix::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let (px, py) = transform.transform(x as f32 + 0.5, y as f32 + 0.5);
+            if !(px >= -1.0 && py >= -1.0 && px < image.width as f32 + 1.0 && py < image.height as f32 + 1.0) {
+                return None;
+            }
+            let px = (px.max(0.0) as usize).min(image.width - 1);
+            let py = (py.max(0.0) as usize).min(image.height - 1);
+            grid.set(x, y, image.get(px, py));
+        }
+    }
+    Some(grid)
+}
@@ -0,0 +1,173 @@
+use crate::color_line_helpers::{first_bar_index, ColorLine};
+use crate::symbology::{Barcode, Symbology};
+
+/// Wide elements of the digits 0-9, first element is in the highest of the 5 bits.
+const ITF_PATTERNS : [u8;10] = [0x06, 0x11, 0x09, 0x18, 0x05, 0x14, 0x0c, 0x03, 0x12, 0x0a];
+
+/// Shortest accepted code. ITF has no mandatory check digit, short codes are mostly false positives.
+const ITF_MIN_DIGITS: usize = 6;
+
+/// Quiet zone has to be at least this many narrow elements wide.
+const ITF_QUIET_ZONE: usize = 6;
+
+/**
+Finds Interleaved 2 of 5 barcodes from one pixel line.
+14 digit codes are reported as ITF-14 and their mod 10 check digit is always validated.
+Bearer bars are not handled separately. Lines along the code cross the sides of a bearer box outside
+the quiet zones, and the rotated lines that enter through the top or the bottom bearer have no quiet zone
+before the bars, so they are not read and the code is found from the other lines.
+Parameters:
+color_line - pixel line the crossings were found from.
+avg_cross - crossings returned by find_crossings_from_average().
+diffs - widths of the bars and spaces between the crossings.
+check_digit - validate mod 10 check digit also for codes that are not 14 digits long.
+**/
+pub fn find_itf(color_line: &ColorLine, avg_cross : &(bool,Vec<usize>), diffs: &[usize], check_digit: bool) -> Vec<Barcode> {
+    let mut found = Vec::new();
+    // Start, digits and stop.
+    let min_len = 4 + ITF_MIN_DIGITS * 5 + 3;
+    let mut r = first_bar_index(avg_cross);
+    while r + min_len <= diffs.len() {
+        let start = &diffs[r..r + 4];
+        let narrow = start.iter().sum::<usize>() as f32 / 4.0;
+        let is_start = start.iter().all(|w| (*w as f32) < narrow * 1.5 && (*w as f32) > narrow * 0.5);
+        let quiet_zone = if r == 0 {avg_cross.1[0]} else {diffs[r - 1]};
+        if is_start && quiet_zone as f32 >= narrow * ITF_QUIET_ZONE as f32 {
+            if let Some((mut code, end)) = read_itf(r + 4, narrow, diffs, check_digit) {
+                code.set_span(color_line.point(avg_cross.1[r]), color_line.point(avg_cross.1[end]));
+                found.push(code);
+                r = end;
+                continue;
+            }
+        }
+        r += 2;
+    }
+    found
+}
+
+/**
+Reads digit pairs after the start pattern until the stop pattern.
+Bars of the pair encode the first digit, spaces encode the second one.
+Returns decoded barcode and the index of the element after the stop pattern.
+**/
+fn read_itf(mut r: usize, narrow: f32, diffs: &[usize], check_digit: bool) -> Option<(Barcode, usize)> {
+    let mut digits: Vec<u8> = Vec::new();
+    loop {
+        if r + 3 > diffs.len() {
+            return None;
+        }
+        if is_itf_stop(r, narrow, diffs) {
+            r += 3;
+            break;
+        }
+        if r + 10 > diffs.len() {
+            return None;
+        }
+        let pair = &diffs[r..r + 10];
+        let bars = [pair[0], pair[2], pair[4], pair[6], pair[8]];
+        let spaces = [pair[1], pair[3], pair[5], pair[7], pair[9]];
+        digits.push(read_itf_digit(&bars)?);
+        digits.push(read_itf_digit(&spaces)?);
+        r += 10;
+    }
+    if digits.len() < ITF_MIN_DIGITS {
+        return None;
+    }
+    let symbology = if digits.len() == 14 {Symbology::Itf14} else {Symbology::Itf};
+    if symbology == Symbology::Itf14 || check_digit {
+        let (check, data) = digits.split_last()?;
+        if calc_mod10_check(data) != *check {
+            return None;
+        }
+    }
+    let text = digits.iter().map(|d| (b'0' + *d) as char).collect();
+    Some((Barcode::new(symbology, text), r))
+}
+
+///Stop pattern is a wide bar, a narrow space and a narrow bar followed by the quiet zone.
+fn is_itf_stop(r: usize, narrow: f32, diffs: &[usize]) -> bool {
+    let stop = &diffs[r..r + 3];
+    let quiet_zone = r + 3 == diffs.len() || diffs[r + 3] as f32 >= narrow * ITF_QUIET_ZONE as f32;
+    quiet_zone
+        && stop[0] as f32 >= narrow * 1.6
+        && (stop[1] as f32) < narrow * 1.5
+        && (stop[2] as f32) < narrow * 1.5
+}
+
+/**
+Digit has 2 wide elements out of 5. The two widest elements are taken as wide
+and they have to be clearly wider than the narrow ones.
+**/
+fn read_itf_digit(lens: &[usize;5]) -> Option<u8> {
+    let mut sorted = *lens;
+    sorted.sort_unstable();
+    let narrow_max = sorted[2];
+    let wide_min = sorted[3];
+    if wide_min * 4 < narrow_max * 5 {
+        return None;
+    }
+    let mut pattern = 0;
+    for l in lens {
+        pattern <<= 1;
+        if *l >= wide_min {
+            pattern |= 1;
+        }
+    }
+    ITF_PATTERNS.iter().position(|p| *p == pattern).map(|d| d as u8)
+}
+
+///GTIN style check digit, weights 3 and 1 starting from the last data digit.
+pub fn calc_mod10_check(digits: &[u8]) -> u8 {
+    let mut sum = 0;
+    for (i, d) in digits.iter().rev().enumerate() {
+        sum += *d as usize * if i % 2 == 0 {3} else {1};
+    }
+    ((10 - sum % 10) % 10) as u8
+}
+
+///Bar and space widths of the digits with the start and stop patterns, wide elements are 3 modules.
+#[cfg(test)]
+pub(crate) fn itf_widths(digits: &str) -> Vec<usize> {
+    let mut widths = vec![1, 1, 1, 1];
+    let digits : Vec<usize> = digits.bytes().map(|d| (d - b'0') as usize).collect();
+    for pair in digits.chunks(2) {
+        for bit in (0..5).rev() {
+            widths.push(if ITF_PATTERNS[pair[0]] >> bit & 1 == 1 {3} else {1});
+            widths.push(if ITF_PATTERNS[pair[1]] >> bit & 1 == 1 {3} else {1});
+        }
+    }
+    widths.extend([3, 1, 1]);
+    widths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder_options::DecoderOptions;
+    use crate::test_images::{read_bearer_box, read_texts};
+
+    fn itf_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::Itf, Symbology::Itf14])
+    }
+
+    #[test]
+    fn reads_itf14_with_check_digit() {
+        assert_eq!(read_texts(&itf_widths("15400141288763"), 0.0, &itf_options()), vec!["ITF-14 15400141288763"]);
+        assert!(read_texts(&itf_widths("15400141288764"), 0.0, &itf_options()).is_empty());
+    }
+
+    #[test]
+    fn reads_itf14_in_bearer_box() {
+        for deg in [0.0, 90.0, 35.0, 200.0] {
+            assert_eq!(read_bearer_box(&itf_widths("15400141288763"), deg, &itf_options()), vec!["ITF-14 15400141288763"], "at {} degrees", deg);
+        }
+    }
+
+    #[test]
+    fn check_digit_of_other_lengths_is_optional() {
+        let options = itf_options().itf_check_digit(true);
+        assert_eq!(read_texts(&itf_widths("12345678"), 0.0, &itf_options()), vec!["ITF 12345678"]);
+        assert!(read_texts(&itf_widths("12345678"), 0.0, &options).is_empty());
+        assert_eq!(read_texts(&itf_widths("12345670"), 0.0, &options), vec!["ITF 12345670"]);
+    }
+}
@@ -0,0 +1,56 @@
+#![allow(clippy::needless_return)]
+//! Barcode reader library. Linear barcodes are read from the scan lines of the image and 2D symbols from the whole image.
+//! Images are read through the PixelValue trait, it is implemented for the `image` crate images with the `image` feature.
+
+mod aztec_decoder;
+mod aztec_reader;
+mod barcode_detector;
+mod barcode_translate;
+mod bit_matrix;
+mod bit_reader;
+mod character_set;
+mod code128_reader;
+mod code39_reader;
+mod code93_reader;
+mod codabar_reader;
+mod color_line_helpers;
+mod dark_area;
+mod databar_reader;
+mod datamatrix_decoder;
+mod datamatrix_reader;
+mod decoder_options;
+mod error;
+mod grid_sampler;
+mod pixel_sources;
+mod itf_reader;
+mod qr_decoder;
+mod qr_reader;
+mod reed_solomon;
+mod symbology;
+#[cfg(test)]
+mod test_images;
+
+#[cfg(feature = "parallel")]
+pub use crate::barcode_detector::process_image_parallel;
+pub use crate::barcode_detector::{process_image_by_rows, process_image_with_options, BarcodeBars, PixelValue};
+pub use crate::barcode_translate::translate_bar_code;
+pub use crate::dark_area::Point;
+pub use crate::decoder_options::{DecoderOptions, Luminance};
+pub use crate::error::{Error, Result};
+pub use crate::pixel_sources::{Frame, PixelFormat, RawImage};
+pub use crate::symbology::{Barcode, ScanPass, Symbology};
+
+/**
+Opens the image file and detects the barcodes from it with the options.
+With the parallel feature the scan lines are read on the rayon threads.
+Returns an error if the file can not be read or its format is not supported.
+**/
+#[cfg(feature = "image")]
+pub fn process_image_file<P: AsRef<std::path::Path>>(path: P, options: &DecoderOptions) -> Result<Vec<Barcode>> {
+    use image::GenericImageView;
+    let img = image::open(path)?;
+    #[cfg(feature = "parallel")]
+    return process_image_parallel(&img, img.dimensions(), options);
+    #[cfg(not(feature = "parallel"))]
+    return process_image_with_options(&img, img.dimensions(), options);
+}
@@ -1,39 +1,29 @@
-use crate::barcode_detector::PixelValue;
-use image::{DynamicImage, GenericImageView};
+use log::debug;
+use rust_barcode_reader::{process_image_file, DecoderOptions, Symbology};
 use std::env;
+use std::process;
 use std::time::Instant;
 
-mod barcode_detector;
-mod barcode_translate;
-mod color_line_helpers;
-
-type BarcodeBarArray = ([usize; 5], [[u8; 4]; 6], [[u8; 4]; 6]);
-
-impl PixelValue for DynamicImage {
-    fn get_pixel_value(&self, x: u32, y: u32, channel: usize, w: usize) -> u8 {
-        return self.get_pixel(x, y).0[channel];
-    }
-}
-
 fn main() {
+    // Diagnostics of the detection are shown with RUST_LOG=debug.
+    env_logger::init();
     let args: Vec<String> = env::args().collect();
-    load_image(&args[1]);
+    if args.len() < 2 {
+        eprintln!("Usage: {} <image file>", args[0]);
+        process::exit(2);
+    }
+    if let Err(e) = load_image(&args[1]) {
+        eprintln!("{}: {}", args[1], e);
+        process::exit(1);
+    }
 }
 
-fn load_image(filename: &String) {
-    println!("Start: {:?}", Instant::now());
-    let img = image::open(&filename).unwrap();
-    println!("Image opened: {:?}", Instant::now());
-
-    let dim = img.dimensions();
-    let barcodes = barcode_detector::process_image_by_rows(&img, dim, 0);
+fn load_image(filename: &str) -> rust_barcode_reader::Result<()> {
+    let start = Instant::now();
+    let barcodes = process_image_file(filename, &DecoderOptions::default().symbologies(&Symbology::ALL).try_channels(true))?;
     for barcode in barcodes {
-        match barcode_translate::translate_bar_code(&barcode) {
-            Some(code) =>  {
-                println!("{:?}", code);
-            },
-            None => {}
-        }
+        println!("{}", barcode);
     }
-    println!("Image processed: {:?}", Instant::now());
+    debug!("{} processed in {:?}", filename, start.elapsed());
+    Ok(())
 }
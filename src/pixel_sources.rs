@@ -0,0 +1,528 @@
+use std::cmp::{max, min};
+use crate::barcode_detector::PixelValue;
+use crate::decoder_options::Luminance;
+use crate::error::{Error, Result};
+
+/**
+Pixels of an 8-bit image in a byte slice, row after row. Channels of one pixel are next to each other.
+    width, height - size of the image in pixels.
+    channels - number of the bytes in one pixel.
+    stride - number of the bytes from the start of one row to the start of the next one.
+**/
+#[derive(Clone, Copy, Debug)]
+pub struct RawImage<'a> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    channels: usize,
+    stride: usize,
+}
+
+impl<'a> RawImage<'a> {
+    ///Returns an error if the stride is shorter than the row or if the data is shorter than the image.
+    pub fn new(data: &'a [u8], width: u32, height: u32, channels: usize, stride: usize) -> Result<RawImage<'a>> {
+        let row_len = width as usize * channels;
+        if channels == 0 || stride < row_len {
+            return Err(Error::InvalidBuffer(format!("stride {} is shorter than {} pixels with {} channels", stride, width, channels)));
+        }
+        let len = if height == 0 {0} else {(height as usize - 1) * stride + row_len};
+        if data.len() < len {
+            return Err(Error::InvalidBuffer(format!("{} bytes are needed, the buffer has {}", len, data.len())));
+        }
+        Ok(RawImage { data, width, height, channels, stride })
+    }
+
+    ///Width and height of the image, as the dim parameter of the detection.
+    pub fn dimensions(&self) -> (u32,u32) {
+        (self.width, self.height)
+    }
+}
+
+impl PixelValue for RawImage<'_> {
+    fn get_pixel_value(&self, x: u32, y: u32, channel: usize) -> u8 {
+        self.data[y as usize * self.stride + x as usize * self.channels + channel]
+    }
+
+    fn channel_count(&self) -> usize {
+        self.channels
+    }
+
+    fn image_size(&self) -> Option<(u32,u32)> {
+        Some(self.dimensions())
+    }
+
+    fn read_line(&self, start: (u32,u32), step: (i32,i32), channel: usize, buf: &mut [u8]) {
+        read_interleaved(self.data, self.stride, self.channels, start, step, channel, buf);
+    }
+}
+
+/**
+Pixel layouts of the raw camera and video frames.
+    Luma8 - one gray byte per pixel.
+    Rgb8, Rgba8, Bgra8 - color bytes in the given order, alpha is not used.
+    Nv12 - Y plane followed by the interleaved U and V plane, only the Y plane is read.
+    Yuyv - Y, U, Y, V bytes for every two pixels (YUY2).
+**/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    Luma8,
+    Rgb8,
+    Rgba8,
+    Bgra8,
+    Nv12,
+    Yuyv,
+}
+
+impl PixelFormat {
+    ///Bytes of one pixel in the first plane.
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Luma8 | PixelFormat::Nv12 => 1,
+            PixelFormat::Yuyv => 2,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 => 4,
+        }
+    }
+
+    ///Offsets of the red, green and blue bytes in the pixel, None for the formats that have the luma byte.
+    fn rgb_offsets(self) -> Option<(usize,usize,usize)> {
+        match self {
+            PixelFormat::Rgb8 | PixelFormat::Rgba8 => Some((0, 1, 2)),
+            PixelFormat::Bgra8 => Some((2, 1, 0)),
+            _ => None,
+        }
+    }
+}
+
+/**
+Raw frame of a camera or a video that is read as a gray image with one channel.
+Luma of the YUV formats is read directly, RGB colors are mixed with the BT.601 weights.
+    width, height - size of the frame in pixels.
+    stride - number of the bytes from the start of one row to the start of the next one, in the first plane.
+    format - layout of the pixels.
+**/
+#[derive(Clone, Copy, Debug)]
+pub struct Frame<'a> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: PixelFormat,
+}
+
+impl<'a> Frame<'a> {
+    /**
+    Returns an error if the stride is shorter than the row or if the data is shorter than the first plane.
+    NV12 data can be only the Y plane, the chroma plane is not read.
+    **/
+    pub fn new(data: &'a [u8], width: u32, height: u32, stride: usize, format: PixelFormat) -> Result<Frame<'a>> {
+        RawImage::new(data, width, height, format.bytes_per_pixel(), stride)?;
+        Ok(Frame { data, width, height, stride, format })
+    }
+
+    ///Width and height of the frame, as the dim parameter of the detection.
+    pub fn dimensions(&self) -> (u32,u32) {
+        (self.width, self.height)
+    }
+}
+
+impl PixelValue for Frame<'_> {
+    fn get_pixel_value(&self, x: u32, y: u32, _channel: usize) -> u8 {
+        let ix = y as usize * self.stride + x as usize * self.format.bytes_per_pixel();
+        match self.format.rgb_offsets() {
+            Some((r, g, b)) => luma_bt601(self.data[ix + r], self.data[ix + g], self.data[ix + b]),
+            None => self.data[ix],
+        }
+    }
+
+    fn channel_count(&self) -> usize {
+        1
+    }
+
+    fn image_size(&self) -> Option<(u32,u32)> {
+        Some(self.dimensions())
+    }
+
+    fn read_line(&self, start: (u32,u32), step: (i32,i32), _channel: usize, buf: &mut [u8]) {
+        let bytes = self.format.bytes_per_pixel();
+        let (r, g, b) = match self.format.rgb_offsets() {
+            Some(offsets) => offsets,
+            None => return read_interleaved(self.data, self.stride, bytes, start, step, 0, buf),
+        };
+        let step = step.0 as isize * bytes as isize + step.1 as isize * self.stride as isize;
+        let mut ix = (start.1 as usize * self.stride + start.0 as usize * bytes) as isize;
+        for value in buf.iter_mut() {
+            let px = &self.data[ix as usize..];
+            *value = luma_bt601(px[r], px[g], px[b]);
+            ix += step;
+        }
+    }
+}
+
+///Luma of the color with the BT.601 weights 0.299, 0.587 and 0.114 in 8-bit fixed point.
+fn luma_bt601(r: u8, g: u8, b: u8) -> u8 {
+    ((77 * r as u32 + 150 * g as u32 + 29 * b as u32 + 128) >> 8) as u8
+}
+
+///Luma of the color with the BT.709 weights 0.2126, 0.7152 and 0.0722 in 8-bit fixed point.
+fn luma_bt709(r: u8, g: u8, b: u8) -> u8 {
+    ((54 * r as u32 + 183 * g as u32 + 19 * b as u32 + 128) >> 8) as u8
+}
+
+/**
+Gray view of the image with one channel, the values are mixed from the color channels of the image.
+Detection reads its channel 0, so the luminance does not depend on the PixelValue implementation.
+**/
+pub(crate) struct LuminanceSource<'a> {
+    img: &'a dyn PixelValue,
+    luminance: Luminance,
+}
+
+impl<'a> LuminanceSource<'a> {
+    pub(crate) fn new(img: &'a dyn PixelValue, luminance: Luminance) -> LuminanceSource<'a> {
+        // Gray images have nothing to mix.
+        let luminance = if img.channel_count() < 3 && luminance != Luminance::Channel(0) {Luminance::Channel(0)} else {luminance};
+        LuminanceSource { img, luminance }
+    }
+
+    fn mix(&self, r: u8, g: u8, b: u8) -> u8 {
+        match self.luminance {
+            Luminance::Bt601 => luma_bt601(r, g, b),
+            Luminance::Bt709 => luma_bt709(r, g, b),
+            Luminance::MaxChannel => max(r, max(g, b)),
+            _ => min(r, min(g, b)),
+        }
+    }
+}
+
+impl PixelValue for LuminanceSource<'_> {
+    fn get_pixel_value(&self, x: u32, y: u32, _channel: usize) -> u8 {
+        match self.luminance {
+            Luminance::Channel(channel) => self.img.get_pixel_value(x, y, channel),
+            _ => self.mix(self.img.get_pixel_value(x, y, 0), self.img.get_pixel_value(x, y, 1), self.img.get_pixel_value(x, y, 2)),
+        }
+    }
+
+    fn channel_count(&self) -> usize {
+        1
+    }
+
+    fn read_line(&self, start: (u32,u32), step: (i32,i32), _channel: usize, buf: &mut [u8]) {
+        if let Luminance::Channel(channel) = self.luminance {
+            return self.img.read_line(start, step, channel, buf);
+        }
+        let mut green = vec![0; buf.len()];
+        let mut blue = vec![0; buf.len()];
+        self.img.read_line(start, step, 0, buf);
+        self.img.read_line(start, step, 1, &mut green);
+        self.img.read_line(start, step, 2, &mut blue);
+        for (i, value) in buf.iter_mut().enumerate() {
+            *value = self.mix(*value, green[i], blue[i]);
+        }
+    }
+}
+
+/**
+Gray values of the whole image in memory, row after row. The image is read once through read_line
+for the passes that need every pixel, the rotated scan lines and the 2D symbols.
+**/
+pub(crate) struct GrayImage {
+    pub(crate) values: Vec<u8>,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl GrayImage {
+    ///Reads the channel of every row of the image.
+    pub(crate) fn read(img: &dyn PixelValue, dim: (u32,u32), channel: usize) -> GrayImage {
+        let mut values = vec![0; dim.0 as usize * dim.1 as usize];
+        for (y, row) in values.chunks_mut(dim.0 as usize).enumerate() {
+            img.read_line((0, y as u32), (1, 0), channel, row);
+        }
+        GrayImage { values, width: dim.0, height: dim.1 }
+    }
+
+    ///Bilinear interpolation from the four nearest pixels, the pixel centers are at the whole coordinates.
+    pub(crate) fn interpolated(&self, p: (f32,f32)) -> u8 {
+        let x = p.0.max(0.0).min((self.width - 1) as f32);
+        let y = p.1.max(0.0).min((self.height - 1) as f32);
+        let (x0, y0) = (x as usize, y as usize);
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let width = self.width as usize;
+        if fx == 0.0 && fy == 0.0 {
+            return self.values[y0 * width + x0];
+        }
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(self.height as usize - 1));
+        let value = |x: usize, y: usize| self.values[y * width + x] as f32;
+        let top = value(x0, y0) * (1.0 - fx) + value(x1, y0) * fx;
+        let bottom = value(x0, y1) * (1.0 - fx) + value(x1, y1) * fx;
+        (top * (1.0 - fy) + bottom * fy + 0.5) as u8
+    }
+}
+
+impl PixelValue for GrayImage {
+    fn get_pixel_value(&self, x: u32, y: u32, _channel: usize) -> u8 {
+        self.values[y as usize * self.width as usize + x as usize]
+    }
+
+    fn channel_count(&self) -> usize {
+        1
+    }
+
+    fn read_line(&self, start: (u32,u32), step: (i32,i32), _channel: usize, buf: &mut [u8]) {
+        read_interleaved(&self.values, self.width as usize, 1, start, step, 0, buf);
+    }
+}
+
+/**
+Copies the pixels of the line straight from the interleaved bytes, the rows of the one channel images at once.
+Parameters:
+stride - number of the bytes in one row.
+channels - number of the bytes in one pixel.
+**/
+fn read_interleaved(data: &[u8], stride: usize, channels: usize, start: (u32,u32), step: (i32,i32), channel: usize, buf: &mut [u8]) {
+    let first = start.1 as usize * stride + start.0 as usize * channels + channel;
+    if step == (1, 0) && channels == 1 {
+        buf.copy_from_slice(&data[first..first + buf.len()]);
+        return;
+    }
+    let step = step.0 as isize * channels as isize + step.1 as isize * stride as isize;
+    let mut ix = first as isize;
+    for value in buf.iter_mut() {
+        *value = data[ix as usize];
+        ix += step;
+    }
+}
+
+#[cfg(feature = "image")]
+impl<P, C> PixelValue for image::ImageBuffer<P, C>
+where
+    P: image::Pixel<Subpixel = u8> + 'static,
+    C: std::ops::Deref<Target = [u8]>,
+{
+    fn get_pixel_value(&self, x: u32, y: u32, channel: usize) -> u8 {
+        self.get_pixel(x, y).channels()[channel]
+    }
+
+    fn channel_count(&self) -> usize {
+        P::CHANNEL_COUNT as usize
+    }
+
+    fn image_size(&self) -> Option<(u32,u32)> {
+        Some(self.dimensions())
+    }
+
+    fn read_line(&self, start: (u32,u32), step: (i32,i32), channel: usize, buf: &mut [u8]) {
+        let channels = P::CHANNEL_COUNT as usize;
+        read_interleaved(self, self.width() as usize * channels, channels, start, step, channel, buf);
+    }
+}
+
+#[cfg(feature = "image")]
+impl PixelValue for image::DynamicImage {
+    fn get_pixel_value(&self, x: u32, y: u32, channel: usize) -> u8 {
+        use image::GenericImageView;
+        return self.get_pixel(x, y).0[channel];
+    }
+
+    fn image_size(&self) -> Option<(u32,u32)> {
+        use image::GenericImageView;
+        Some(self.dimensions())
+    }
+
+    ///8-bit images are read by their own read_line, as RGBA like get_pixel_value. Gray is in all the color channels.
+    fn read_line(&self, start: (u32,u32), step: (i32,i32), channel: usize, buf: &mut [u8]) {
+        use image::DynamicImage::*;
+        match (self, channel) {
+            (ImageLuma8(_), 3) | (ImageRgb8(_), 3) => buf.fill(255),
+            (ImageLuma8(img), _) => img.read_line(start, step, 0, buf),
+            (ImageLumaA8(img), 3) => img.read_line(start, step, 1, buf),
+            (ImageLumaA8(img), _) => img.read_line(start, step, 0, buf),
+            (ImageRgb8(img), _) => img.read_line(start, step, channel, buf),
+            (ImageRgba8(img), _) => img.read_line(start, step, channel, buf),
+            _ => {
+                let (mut x, mut y) = (start.0 as i64, start.1 as i64);
+                for value in buf.iter_mut() {
+                    *value = self.get_pixel_value(x as u32, y as u32, channel);
+                    x += step.0 as i64;
+                    y += step.1 as i64;
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///Pixel values of the test images, every pixel and channel has its own value.
+    fn pattern(width: u32, height: u32, channels: usize) -> Vec<u8> {
+        (0..(width * height) as usize * channels).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    ///Checks that the rows, the columns and the lines read backwards match get_pixel_value.
+    fn assert_lines_match(img: &dyn PixelValue, dim: (u32,u32)) {
+        for channel in 0..img.channel_count() {
+            let lines = [((0, 1), (1, 0), dim.0), ((2, 0), (0, 1), dim.1), ((dim.0 - 1, dim.1 - 1), (-1, -1), dim.0.min(dim.1))];
+            for (start, step, len) in lines.iter() {
+                let mut buf = vec![0; *len as usize];
+                img.read_line(*start, *step, channel, &mut buf);
+                let expected : Vec<u8> = (0..*len as i32).map(|i| {
+                    img.get_pixel_value((start.0 as i32 + step.0 * i) as u32, (start.1 as i32 + step.1 * i) as u32, channel)
+                }).collect();
+                assert_eq!(buf, expected, "channel {} from {:?} step {:?}", channel, start, step);
+            }
+        }
+    }
+
+    #[test]
+    fn raw_image_lines_match_pixels() {
+        let (w, h) = (7, 5);
+        for channels in 1..=4 {
+            let stride = w as usize * channels + 3;
+            let data = pattern(stride as u32, h, 1);
+            let img = RawImage::new(&data, w, h, channels, stride).unwrap();
+            assert_lines_match(&img, img.dimensions());
+        }
+    }
+
+    ///Frame bytes of the colors in the format, every row is followed by the padding up to the stride.
+    fn frame_bytes(colors: &[(u8,u8,u8)], width: usize, format: PixelFormat, stride: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        for row in colors.chunks(width) {
+            let start = data.len();
+            for (x, (r, g, b)) in row.iter().enumerate() {
+                let luma = luma_bt601(*r, *g, *b);
+                match format {
+                    PixelFormat::Luma8 | PixelFormat::Nv12 => data.push(luma),
+                    PixelFormat::Yuyv => data.extend([luma, if x % 2 == 0 {90} else {240}]),
+                    PixelFormat::Rgb8 => data.extend([*r, *g, *b]),
+                    PixelFormat::Rgba8 => data.extend([*r, *g, *b, 17]),
+                    PixelFormat::Bgra8 => data.extend([*b, *g, *r, 17]),
+                }
+            }
+            data.resize(start + stride, 0xaa);
+        }
+        if format == PixelFormat::Nv12 {
+            data.extend(vec![128; stride * colors.len() / width / 2]);
+        }
+        data
+    }
+
+    #[test]
+    fn frames_read_the_luma() {
+        let (w, h) = (6, 4);
+        let colors : Vec<(u8,u8,u8)> = pattern(w, h, 3).chunks(3).map(|c| (c[0], c[1], c[2])).collect();
+        let formats = [PixelFormat::Luma8, PixelFormat::Rgb8, PixelFormat::Rgba8, PixelFormat::Bgra8, PixelFormat::Nv12, PixelFormat::Yuyv];
+        for format in formats.iter() {
+            let stride = w as usize * format.bytes_per_pixel() + 5;
+            let data = frame_bytes(&colors, w as usize, *format, stride);
+            let frame = Frame::new(&data, w, h, stride, *format).unwrap();
+            for (i, (r, g, b)) in colors.iter().enumerate() {
+                assert_eq!(frame.get_pixel_value(i as u32 % w, i as u32 / w, 0), luma_bt601(*r, *g, *b), "{:?}", format);
+            }
+            assert_lines_match(&frame, frame.dimensions());
+        }
+    }
+
+    #[test]
+    fn short_buffers_are_rejected() {
+        let data = vec![0; 40];
+        assert!(matches!(RawImage::new(&data, 5, 4, 3, 14), Err(Error::InvalidBuffer(_))));
+        assert!(matches!(RawImage::new(&data, 5, 4, 2, 12), Err(Error::InvalidBuffer(_))));
+        assert!(RawImage::new(&data, 5, 4, 2, 10).is_ok());
+        assert!(matches!(Frame::new(&data, 5, 4, 16, PixelFormat::Bgra8), Err(Error::InvalidBuffer(_))));
+        assert!(Frame::new(&data, 5, 4, 10, PixelFormat::Yuyv).is_ok());
+    }
+
+    #[test]
+    fn reads_code_from_padded_frames() {
+        use crate::barcode_detector::process_image_with_options;
+        use crate::code39_reader::code39_widths;
+        use crate::decoder_options::DecoderOptions;
+        use crate::test_images::draw_widths;
+
+        let (pixels, w, h) = draw_widths(&code39_widths("FRAME"), 0.0);
+        // Dark bars are blue on yellow, the luma is still dark on light.
+        let colors : Vec<(u8,u8,u8)> = pixels.iter().map(|p| if *p < 128 {(20, 30, 160)} else {(250, 230, 60)}).collect();
+        for format in [PixelFormat::Bgra8, PixelFormat::Rgb8, PixelFormat::Nv12, PixelFormat::Yuyv].iter() {
+            let stride = w as usize * format.bytes_per_pixel() + 16;
+            let data = frame_bytes(&colors, w as usize, *format, stride);
+            let frame = Frame::new(&data, w, h, stride, *format).unwrap();
+            let codes = process_image_with_options(&frame, frame.dimensions(), &DecoderOptions::default()).unwrap();
+            assert_eq!(codes.iter().map(|c| c.text.as_str()).collect::<Vec<_>>(), vec!["FRAME"], "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn luminance_mixes_the_channels() {
+        let data = [200, 100, 30];
+        let img = RawImage::new(&data, 1, 1, 3, 3).unwrap();
+        let modes = [
+            (Luminance::Bt601, luma_bt601(200, 100, 30)),
+            (Luminance::Bt709, luma_bt709(200, 100, 30)),
+            (Luminance::MaxChannel, 200),
+            (Luminance::MinChannel, 30),
+            (Luminance::Channel(1), 100),
+            (Luminance::Channel(2), 30),
+        ];
+        for (luminance, expected) in modes.iter() {
+            let source = LuminanceSource::new(&img, *luminance);
+            let mut buf = [0];
+            source.read_line((0, 0), (1, 0), 0, &mut buf);
+            assert_eq!((source.get_pixel_value(0, 0, 0), buf[0]), (*expected, *expected), "{:?}", luminance);
+        }
+        assert_eq!(luma_bt601(200, 100, 30), 122);
+        assert_eq!(luma_bt709(200, 100, 30), 116);
+    }
+
+    #[test]
+    fn gray_images_read_channel_zero() {
+        let data = pattern(4, 3, 2);
+        let img = RawImage::new(&data, 4, 3, 2, 8).unwrap();
+        for luminance in [Luminance::Bt601, Luminance::MaxChannel, Luminance::MinChannel].iter() {
+            let source = LuminanceSource::new(&img, *luminance);
+            assert_eq!(source.get_pixel_value(1, 2, 0), img.get_pixel_value(1, 2, 0), "{:?}", luminance);
+            assert_lines_match(&source, (4, 3));
+        }
+    }
+
+    #[test]
+    fn codes_in_one_channel_need_the_channel() {
+        use crate::barcode_detector::process_image_with_options;
+        use crate::code39_reader::code39_widths;
+        use crate::decoder_options::DecoderOptions;
+        use crate::test_images::draw_widths;
+
+        let (pixels, w, h) = draw_widths(&code39_widths("RED"), 0.0);
+        // Red bars on green have nearly the same luma, only the red channel shows the code.
+        let data : Vec<u8> = pixels.iter().flat_map(|p| if *p < 128 {[0, 128, 0]} else {[255, 0, 0]}).collect();
+        let img = RawImage::new(&data, w, h, 3, w as usize * 3).unwrap();
+        let read = |options: DecoderOptions| -> Vec<String> {
+            process_image_with_options(&img, (w, h), &options).unwrap().into_iter().map(|c| c.text).collect()
+        };
+        assert!(read(DecoderOptions::default()).is_empty());
+        assert_eq!(read(DecoderOptions::default().luminance(Luminance::Channel(0))), vec!["RED"]);
+        assert_eq!(read(DecoderOptions::default().luminance(Luminance::MaxChannel)), vec!["RED"]);
+        assert_eq!(read(DecoderOptions::default().try_channels(true)), vec!["RED"]);
+        let options = DecoderOptions::default().luminance(Luminance::Channel(3));
+        assert!(matches!(process_image_with_options(&img, (w, h), &options), Err(Error::InvalidChannel(3, 3))));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn dynamic_image_lines_match_pixels() {
+        let (w, h) = (7, 5);
+        let images = [
+            image::DynamicImage::ImageLuma8(image::ImageBuffer::from_raw(w, h, pattern(w, h, 1)).unwrap()),
+            image::DynamicImage::ImageLumaA8(image::ImageBuffer::from_raw(w, h, pattern(w, h, 2)).unwrap()),
+            image::DynamicImage::ImageRgb8(image::ImageBuffer::from_raw(w, h, pattern(w, h, 3)).unwrap()),
+            image::DynamicImage::ImageRgba8(image::ImageBuffer::from_raw(w, h, pattern(w, h, 4)).unwrap()),
+            image::DynamicImage::ImageBgr8(image::ImageBuffer::from_raw(w, h, pattern(w, h, 3)).unwrap()),
+        ];
+        for img in images.iter() {
+            assert_lines_match(img, (w, h));
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use crate::bit_matrix::BitMatrix;
+use crate::bit_reader::BitReader;
+use crate::character_set::decode_bytes;
+use crate::reed_solomon::{correct_errors, GaloisField};
+
+/// Error correction codewords per block by the error correction level (L, M, Q, H) and version.
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [0,  7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+
+/// Number of the error correction blocks by the error correction level (L, M, Q, H) and version.
+const NUM_ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4,  4,  4,  4,  4,  6,  6,  6,  6,  7,  8,  8,  9,  9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5,  5,  8,  9,  9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8,  8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+
+/// Error correction level index in the tables above by the 2 level bits of the format information.
+const EC_LEVEL_BY_FORMAT_BITS: [usize; 4] = [1, 0, 3, 2];
+
+/// Format information is XORed with this so that it is never all light.
+const FORMAT_INFO_MASK: u32 = 0x5412;
+
+const ALPHANUMERIC_CHARS: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Group separator that replaces FNC1 in the GS1 data.
+const GS: char = '\u{1d}';
+
+/**
+Decodes the sampled QR Code modules to text.
+Returns None if the format information can not be read or there are too many errors.
+Parameters:
+grid - one bit per module, size has to match the version.
+**/
+pub fn decode_qr(grid: &BitMatrix) -> Option<String> {
+    let dim = grid.width;
+    if !(21..=177).contains(&dim) || dim % 4 != 1 || grid.height != dim {
+        return None;
+    }
+    let version = (dim - 17) / 4;
+    if version >= 7 && read_version(grid) != Some(version) {
+        return None;
+    }
+    let (ec_level, mask) = read_format_info(grid)?;
+    let codewords = read_codewords(grid, version, mask)?;
+    let data = correct_blocks(&codewords, version, ec_level)?;
+    decode_bitstream(&data, version)
+}
+
+/**
+Error correction level and data mask from either of the two format information copies.
+Up to 3 wrong bits are corrected.
+**/
+fn read_format_info(grid: &BitMatrix) -> Option<(usize, usize)> {
+    let dim = grid.width;
+    let mut first = 0;
+    let mut second = 0;
+    // First copy is around the top left finder, second one is split between the other two.
+    for x in 0..6 {
+        first = first << 1 | grid.get(x, 8) as u32;
+    }
+    first = first << 1 | grid.get(7, 8) as u32;
+    first = first << 1 | grid.get(8, 8) as u32;
+    first = first << 1 | grid.get(8, 7) as u32;
+    for y in (0..6).rev() {
+        first = first << 1 | grid.get(8, y) as u32;
+    }
+    for y in (dim - 7..dim).rev() {
+        second = second << 1 | grid.get(8, y) as u32;
+    }
+    for x in dim - 8..dim {
+        second = second << 1 | grid.get(x, 8) as u32;
+    }
+    let mut best = (4, 0);
+    for data in 0..32 {
+        let code = bch_code(data, 10, 0x537) ^ FORMAT_INFO_MASK;
+        let distance = ((first ^ code).count_ones()).min((second ^ code).count_ones());
+        if distance < best.0 {
+            best = (distance, data);
+        }
+    }
+    if best.0 > 3 {
+        return None;
+    }
+    Some((EC_LEVEL_BY_FORMAT_BITS[(best.1 >> 3) as usize], (best.1 & 7) as usize))
+}
+
+/**
+Version from the version information blocks next to the top right and bottom left finders.
+Only versions 7 and above have them. Up to 3 wrong bits are corrected.
+**/
+pub fn read_version(grid: &BitMatrix) -> Option<usize> {
+    let dim = grid.width;
+    if dim < 45 || grid.height != dim {
+        return None;
+    }
+    let mut top_right = 0;
+    let mut bottom_left = 0;
+    for a in (0..6).rev() {
+        for b in (dim - 11..dim - 8).rev() {
+            top_right = top_right << 1 | grid.get(b, a) as u32;
+            bottom_left = bottom_left << 1 | grid.get(a, b) as u32;
+        }
+    }
+    let mut best = (4, 0);
+    for version in 7..=40 {
+        let code = bch_code(version, 12, 0x1f25);
+        let distance = ((top_right ^ code).count_ones()).min((bottom_left ^ code).count_ones());
+        if distance < best.0 {
+            best = (distance, version);
+        }
+    }
+    if best.0 > 3 {
+        return None;
+    }
+    Some(best.1 as usize)
+}
+
+///Data bits followed by the remainder of the BCH code with the given generator polynomial.
+fn bch_code(data: u32, ec_bits: usize, generator: u32) -> u32 {
+    let mut rem = data;
+    for _ in 0..ec_bits {
+        rem = (rem << 1) ^ ((rem >> (ec_bits - 1)) * generator);
+    }
+    data << ec_bits | rem
+}
+
+/**
+Reads the codewords in the two module wide columns from the bottom right corner, zig-zagging
+up and down and skipping the function patterns. Data mask is removed while reading.
+**/
+fn read_codewords(grid: &BitMatrix, version: usize, mask: usize) -> Option<Vec<u8>> {
+    let dim = grid.width;
+    let function = function_pattern(version);
+    let mut codewords = Vec::with_capacity(raw_codewords(version));
+    let mut current = 0_u8;
+    let mut bits = 0;
+    let mut reading_up = true;
+    let mut right = dim - 1;
+    while right > 0 {
+        // Vertical timing pattern takes the whole column.
+        if right == 6 {
+            right -= 1;
+        }
+        for count in 0..dim {
+            let y = if reading_up {dim - 1 - count} else {count};
+            for x in [right, right - 1] {
+                if function.get(x, y) {
+                    continue;
+                }
+                current = current << 1 | (grid.get(x, y) ^ is_masked(mask, y, x)) as u8;
+                bits += 1;
+                if bits == 8 {
+                    codewords.push(current);
+                    current = 0;
+                    bits = 0;
+                }
+            }
+        }
+        reading_up = !reading_up;
+        right = right.saturating_sub(2);
+    }
+    if codewords.len() != raw_codewords(version) {
+        return None;
+    }
+    Some(codewords)
+}
+
+fn is_masked(mask: usize, row: usize, col: usize) -> bool {
+    match mask {
+        0 => (row + col).is_multiple_of(2),
+        1 => row.is_multiple_of(2),
+        2 => col.is_multiple_of(3),
+        3 => (row + col).is_multiple_of(3),
+        4 => (row / 2 + col / 3).is_multiple_of(2),
+        5 => (row * col % 2 + row * col % 3) == 0,
+        6 => (row * col % 2 + row * col % 3).is_multiple_of(2),
+        _ => ((row + col) % 2 + row * col % 3).is_multiple_of(2),
+    }
+}
+
+///Modules of the finders, separators, timing and alignment patterns, format and version information.
+fn function_pattern(version: usize) -> BitMatrix {
+    let dim = version * 4 + 17;
+    let mut function = BitMatrix::new(dim, dim);
+    let mut set_region = |x: usize, y: usize, w: usize, h: usize| {
+        for yy in y..y + h {
+            for xx in x..x + w {
+                function.set(xx, yy, true);
+            }
+        }
+    };
+    set_region(0, 0, 9, 9);
+    set_region(dim - 8, 0, 8, 9);
+    set_region(0, dim - 8, 9, 8);
+    set_region(6, 9, 1, dim - 17);
+    set_region(9, 6, dim - 17, 1);
+    let positions = alignment_pattern_positions(version);
+    let last = positions.len().saturating_sub(1);
+    for (i, x) in positions.iter().enumerate() {
+        for (j, y) in positions.iter().enumerate() {
+            // Corners with the finder patterns.
+            if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                continue;
+            }
+            set_region(x - 2, y - 2, 5, 5);
+        }
+    }
+    if version >= 7 {
+        set_region(dim - 11, 0, 3, 6);
+        set_region(0, dim - 11, 6, 3);
+    }
+    function
+}
+
+///Row and column coordinates of the alignment pattern centers.
+pub fn alignment_pattern_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = if version == 32 {26} else {(version * 4 + count * 2 + 1) / (count * 2 - 2) * 2};
+    let dim = version * 4 + 17;
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| dim - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+///Number of the data and error correction codewords in the symbol.
+fn raw_codewords(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let count = version / 7 + 2;
+        modules -= (25 * count - 10) * count - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules / 8
+}
+
+/**
+Splits the interleaved codewords to the error correction blocks, corrects them and
+returns the data codewords. Short blocks come first, long blocks have one more data codeword.
+**/
+fn correct_blocks(codewords: &[u8], version: usize, ec_level: usize) -> Option<Vec<u8>> {
+    let num_blocks = NUM_ERROR_CORRECTION_BLOCKS[ec_level][version] as usize;
+    let ecc_len = ECC_CODEWORDS_PER_BLOCK[ec_level][version] as usize;
+    let raw = codewords.len();
+    let num_short = num_blocks - raw % num_blocks;
+    let short_len = raw / num_blocks;
+    let short_data_len = short_len - ecc_len;
+    let mut blocks: Vec<Vec<u16>> = vec![Vec::with_capacity(short_len + 1); num_blocks];
+    let mut next = codewords.iter();
+    for i in 0..=short_len {
+        for (j, block) in blocks.iter_mut().enumerate() {
+            if i == short_data_len && j < num_short {
+                continue;
+            }
+            block.push(*next.next()? as u16);
+        }
+    }
+    let field = GaloisField::new(0x11d, 256, 0);
+    let mut data = Vec::with_capacity(raw - ecc_len * num_blocks);
+    for block in blocks.iter_mut() {
+        correct_errors(&field, block, ecc_len)?;
+        data.extend(block[..block.len() - ecc_len].iter().map(|c| *c as u8));
+    }
+    Some(data)
+}
+
+/**
+Decodes the data segments. Byte segments use the character set of the preceding ECI,
+without ECI they are read as UTF-8 if valid and otherwise as ISO-8859-1.
+FNC1 in the first position marks GS1 data, its FNC1 characters are returned as GS.
+**/
+fn decode_bitstream(data: &[u8], version: usize) -> Option<String> {
+    let mut reader = BitReader::new(data);
+    let mut text = String::new();
+    let mut eci: Option<u32> = None;
+    let mut fnc1 = false;
+    // Character count length grows for versions 10 and 27.
+    let size_class = if version <= 9 {0} else if version <= 26 {1} else {2};
+    while reader.available() >= 4 {
+        let mode = reader.read(4)?;
+        match mode {
+            0 => break,
+            1 => {
+                let count = reader.read([10, 12, 14][size_class])? as usize;
+                decode_numeric(&mut reader, count, &mut text)?;
+            }
+            2 => {
+                let count = reader.read([9, 11, 13][size_class])? as usize;
+                decode_alphanumeric(&mut reader, count, fnc1, &mut text)?;
+            }
+            4 => {
+                let count = reader.read([8, 16, 16][size_class])? as usize;
+                let mut bytes = Vec::with_capacity(count);
+                for _ in 0..count {
+                    bytes.push(reader.read(8)? as u8);
+                }
+                text.push_str(&decode_bytes(&bytes, eci)?);
+            }
+            8 => {
+                let count = reader.read([8, 10, 12][size_class])? as usize;
+                let mut bytes = Vec::with_capacity(count * 2);
+                for _ in 0..count {
+                    // Shift JIS ranges 0x8140-0x9FFC and 0xE040-0xEBBF are packed to 13 bits.
+                    let value = reader.read(13)?;
+                    let mut two_bytes = (value / 0xc0) << 8 | (value % 0xc0);
+                    two_bytes += if two_bytes < 0x1f00 {0x8140} else {0xc140};
+                    bytes.push((two_bytes >> 8) as u8);
+                    bytes.push(two_bytes as u8);
+                }
+                text.push_str(&encoding_rs::SHIFT_JIS.decode_without_bom_handling(&bytes).0);
+            }
+            13 => {
+                // Hanzi mode, only the GB 2312 subset is defined.
+                if reader.read(4)? != 1 {
+                    return None;
+                }
+                let count = reader.read([8, 10, 12][size_class])? as usize;
+                let mut bytes = Vec::with_capacity(count * 2);
+                for _ in 0..count {
+                    let value = reader.read(13)?;
+                    let mut two_bytes = (value / 0x60) << 8 | (value % 0x60);
+                    two_bytes += if two_bytes < 0xa00 {0xa1a1} else {0xa6a1};
+                    bytes.push((two_bytes >> 8) as u8);
+                    bytes.push(two_bytes as u8);
+                }
+                text.push_str(&encoding_rs::GBK.decode_without_bom_handling(&bytes).0);
+            }
+            7 => eci = Some(read_eci(&mut reader)?),
+            // Structured append, position and parity of the symbol are not needed for the text.
+            3 => {
+                reader.read(16)?;
+            }
+            5 => fnc1 = true,
+            9 => {
+                // Application indicator of FNC1 in the second position.
+                reader.read(8)?;
+                fnc1 = true;
+            }
+            _ => return None,
+        }
+    }
+    Some(text)
+}
+
+fn decode_numeric(reader: &mut BitReader, mut count: usize, text: &mut String) -> Option<()> {
+    while count > 0 {
+        let (bits, digits) = match count {
+            1 => (4, 1),
+            2 => (7, 2),
+            _ => (10, 3),
+        };
+        let value = reader.read(bits)?;
+        let formatted = format!("{:0width$}", value, width = digits);
+        if formatted.len() != digits {
+            return None;
+        }
+        text.push_str(&formatted);
+        count -= digits;
+    }
+    Some(())
+}
+
+fn decode_alphanumeric(reader: &mut BitReader, mut count: usize, fnc1: bool, text: &mut String) -> Option<()> {
+    let mut chars = Vec::with_capacity(count);
+    while count > 1 {
+        let value = reader.read(11)? as usize;
+        chars.push(*ALPHANUMERIC_CHARS.get(value / 45)? as char);
+        chars.push(*ALPHANUMERIC_CHARS.get(value % 45)? as char);
+        count -= 2;
+    }
+    if count == 1 {
+        chars.push(*ALPHANUMERIC_CHARS.get(reader.read(6)? as usize)? as char);
+    }
+    if !fnc1 {
+        text.extend(chars);
+        return Some(());
+    }
+    // In GS1 data % is FNC1 and %% is the percent sign.
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '%' {
+            if chars.get(i + 1) == Some(&'%') {
+                text.push('%');
+                i += 1;
+            } else {
+                text.push(GS);
+            }
+        } else {
+            text.push(chars[i]);
+        }
+        i += 1;
+    }
+    Some(())
+}
+
+///ECI assignment number, 1 to 3 bytes long depending on the high bits of the first byte.
+fn read_eci(reader: &mut BitReader) -> Option<u32> {
+    let first = reader.read(8)?;
+    if first & 0x80 == 0 {
+        Some(first & 0x7f)
+    } else if first & 0xc0 == 0x80 {
+        Some((first & 0x3f) << 8 | reader.read(8)?)
+    } else if first & 0xe0 == 0xc0 {
+        Some((first & 0x1f) << 16 | reader.read(16)?)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,485 @@
+use crate::bit_matrix::BitMatrix;
+use crate::grid_sampler::{sample_grid, PerspectiveTransform};
+use crate::qr_decoder::{decode_qr, read_version};
+use crate::symbology::{Barcode, Symbology};
+
+/// Rows are scanned with a step that finds finder patterns of the largest version with this many modules.
+const QR_MAX_MODULES: usize = 97;
+
+/// Smallest row step in pixels.
+const QR_MIN_ROW_STEP: usize = 3;
+
+/// Only this many best finder pattern candidates are combined to the symbols.
+const QR_MAX_CANDIDATES: usize = 20;
+
+/// Alignment pattern may have this many of its 25 modules wrong.
+const ALIGNMENT_MAX_WRONG: usize = 2;
+
+/**
+Possible finder pattern, center of the 7x7 module square with the 3x3 dark center.
+    x, y - center in the image.
+    module_size - estimated module width in pixels.
+    count - number of the rows and columns the pattern was confirmed on.
+**/
+#[derive(Clone, Copy, Debug)]
+struct FinderPattern {
+    x: f32,
+    y: f32,
+    module_size: f32,
+    count: usize,
+}
+
+impl FinderPattern {
+    fn about_equals(&self, module_size: f32, x: f32, y: f32) -> bool {
+        if (y - self.y).abs() > module_size || (x - self.x).abs() > module_size {
+            return false;
+        }
+        let size_diff = (module_size - self.module_size).abs();
+        size_diff <= 1.0 || size_diff <= self.module_size
+    }
+
+    ///Weighted average of the earlier detections and the new one.
+    fn combine(&mut self, module_size: f32, x: f32, y: f32) {
+        let count = self.count as f32;
+        self.x = (self.x * count + x) / (count + 1.0);
+        self.y = (self.y * count + y) / (count + 1.0);
+        self.module_size = (self.module_size * count + module_size) / (count + 1.0);
+        self.count += 1;
+    }
+}
+
+/**
+Finds and decodes QR Codes from the binarized image.
+Finder patterns are searched from the rows and confirmed from the columns, every
+suitable triple of them is tried as the corners of a symbol.
+Parameters:
+image - binarized image.
+**/
+pub fn find_qr_codes(image: &BitMatrix) -> Vec<Barcode> {
+    let mut found = Vec::new();
+    let mut patterns = find_finder_patterns(image);
+    // Patterns seen on one row only are mostly noise, unless there are no better ones.
+    if patterns.iter().filter(|p| p.count >= 2).count() >= 3 {
+        patterns.retain(|p| p.count >= 2);
+    }
+    patterns.sort_by_key(|p| std::cmp::Reverse(p.count));
+    patterns.truncate(QR_MAX_CANDIDATES);
+    let mut used = vec![false; patterns.len()];
+    for i in 0..patterns.len() {
+        for j in i + 1..patterns.len() {
+            for k in j + 1..patterns.len() {
+                if used[i] || used[j] || used[k] {
+                    continue;
+                }
+                if let Some(corners) = order_finder_patterns(&patterns[i], &patterns[j], &patterns[k]) {
+                    if let Some(text) = decode_symbol(image, &corners) {
+                        used[i] = true;
+                        used[j] = true;
+                        used[k] = true;
+                        found.push(Barcode::new(Symbology::QrCode, text));
+                    }
+                }
+            }
+        }
+    }
+    found
+}
+
+/**
+Scans the rows for the dark-light-dark-light-dark runs with the widths 1:1:3:1:1
+that are the horizontal cross section of the finder pattern.
+**/
+fn find_finder_patterns(image: &BitMatrix) -> Vec<FinderPattern> {
+    let mut patterns: Vec<FinderPattern> = Vec::new();
+    let step = (3 * image.height / (4 * QR_MAX_MODULES)).max(QR_MIN_ROW_STEP);
+    let mut y = step - 1;
+    while y < image.height {
+        let mut state = [0_usize; 5];
+        let mut current = 0;
+        for x in 0..image.width {
+            if image.get(x, y) {
+                // Light run ended.
+                if current % 2 == 1 {
+                    current += 1;
+                }
+                state[current] += 1;
+            } else if current % 2 == 1 {
+                state[current] += 1;
+            } else if current < 4 {
+                current += 1;
+                state[current] += 1;
+            } else {
+                if is_finder_cross(&state) && handle_possible_center(image, &state, x, y, &mut patterns) {
+                    state = [0; 5];
+                    current = 0;
+                    continue;
+                }
+                // Last dark-light pair can be the start of the next pattern.
+                state = [state[2], state[3], state[4], 1, 0];
+                current = 3;
+            }
+        }
+        if is_finder_cross(&state) {
+            handle_possible_center(image, &state, image.width, y, &mut patterns);
+        }
+        y += step;
+    }
+    patterns
+}
+
+///Run widths have the 1:1:3:1:1 ratio with half a module tolerance.
+fn is_finder_cross(state: &[usize; 5]) -> bool {
+    if state.contains(&0) {
+        return false;
+    }
+    let total: usize = state.iter().sum();
+    if total < 7 {
+        return false;
+    }
+    let module = total as f32 / 7.0;
+    let max_variance = module / 2.0;
+    (module - state[0] as f32).abs() < max_variance
+        && (module - state[1] as f32).abs() < max_variance
+        && (3.0 * module - state[2] as f32).abs() < 3.0 * max_variance
+        && (module - state[3] as f32).abs() < max_variance
+        && (module - state[4] as f32).abs() < max_variance
+}
+
+///Center of the middle run when the runs end at position end.
+fn center_from_end(state: &[usize], end: usize) -> f32 {
+    let after: usize = state[state.len() / 2 + 1..].iter().sum();
+    end as f32 - after as f32 - state[state.len() / 2] as f32 / 2.0
+}
+
+/**
+Confirms the row pattern from the column and again from the row through the found center.
+Returns true if the pattern was confirmed, it is added to patterns or combined with an earlier one.
+**/
+fn handle_possible_center(image: &BitMatrix, state: &[usize; 5], end: usize, y: usize, patterns: &mut Vec<FinderPattern>) -> bool {
+    let total: usize = state.iter().sum();
+    let center_x = center_from_end(state, end);
+    let center_y = match cross_check(image, center_x as usize, y, true, state[2], total) {
+        Some(center_y) => center_y,
+        None => return false,
+    };
+    let center_x = match cross_check(image, center_x as usize, center_y as usize, false, state[2], total) {
+        Some(center_x) => center_x,
+        None => return false,
+    };
+    let module_size = total as f32 / 7.0;
+    match patterns.iter_mut().find(|p| p.about_equals(module_size, center_x, center_y)) {
+        Some(pattern) => pattern.combine(module_size, center_x, center_y),
+        None => patterns.push(FinderPattern { x: center_x, y: center_y, module_size, count: 1 }),
+    }
+    true
+}
+
+/**
+Counts the finder pattern runs to both directions from the point in the middle run.
+Returns the center coordinate along the checked line.
+Parameters:
+vertical - check along the column, otherwise along the row.
+max_count - longest accepted outer run.
+original_total - width of the pattern on the line it was found from, the size may not differ much.
+**/
+fn cross_check(image: &BitMatrix, x: usize, y: usize, vertical: bool, max_count: usize, original_total: usize) -> Option<f32> {
+    let get = |p: i32| if vertical {image.get_checked(x as i32, p)} else {image.get_checked(p, y as i32)};
+    let start = if vertical {y as i32} else {x as i32};
+    let limit = if vertical {image.height as i32} else {image.width as i32};
+    let mut state = [0_usize; 5];
+    let mut p = start;
+    while p >= 0 && get(p) {
+        state[2] += 1;
+        p -= 1;
+    }
+    while p >= 0 && !get(p) && state[1] <= max_count {
+        state[1] += 1;
+        p -= 1;
+    }
+    if p < 0 || state[1] > max_count {
+        return None;
+    }
+    while p >= 0 && get(p) && state[0] <= max_count {
+        state[0] += 1;
+        p -= 1;
+    }
+    if state[0] > max_count {
+        return None;
+    }
+    p = start + 1;
+    while p < limit && get(p) {
+        state[2] += 1;
+        p += 1;
+    }
+    while p < limit && !get(p) && state[3] < max_count {
+        state[3] += 1;
+        p += 1;
+    }
+    if p == limit || state[3] >= max_count {
+        return None;
+    }
+    while p < limit && get(p) && state[4] < max_count {
+        state[4] += 1;
+        p += 1;
+    }
+    if state[4] >= max_count {
+        return None;
+    }
+    let total: usize = state.iter().sum();
+    if 5 * total.abs_diff(original_total) >= 2 * original_total || !is_finder_cross(&state) {
+        return None;
+    }
+    Some(center_from_end(&state, p as usize))
+}
+
+/**
+Orders three finder patterns to top left, top right and bottom left corner.
+Top left is opposite to the longest side, the others are told apart by the cross product.
+Returns None if the patterns do not form a right isosceles triangle with similar module sizes.
+**/
+fn order_finder_patterns(a: &FinderPattern, b: &FinderPattern, c: &FinderPattern) -> Option<[FinderPattern; 3]> {
+    let max_size = a.module_size.max(b.module_size).max(c.module_size);
+    let min_size = a.module_size.min(b.module_size).min(c.module_size);
+    if max_size > min_size * 1.5 {
+        return None;
+    }
+    let dist = |p: &FinderPattern, q: &FinderPattern| ((p.x - q.x).powi(2) + (p.y - q.y).powi(2)).sqrt();
+    let (ab, bc, ac) = (dist(a, b), dist(b, c), dist(a, c));
+    let (mut first, top_left, mut last, hypotenuse, side1, side2) = if bc >= ab && bc >= ac {
+        (*b, *a, *c, bc, ab, ac)
+    } else if ac >= ab && ac >= bc {
+        (*a, *b, *c, ac, ab, bc)
+    } else {
+        (*a, *c, *b, ab, ac, bc)
+    };
+    // Sides can differ because of the perspective, but not much.
+    if (side1 - side2).abs() > side1.min(side2) * 0.25 {
+        return None;
+    }
+    let expected = (side1 * side1 + side2 * side2).sqrt();
+    if (hypotenuse - expected).abs() > expected * 0.2 {
+        return None;
+    }
+    // Symbol has at least 14 modules between the finder centers.
+    if side1.min(side2) < 14.0 * min_size * 0.75 {
+        return None;
+    }
+    let cross = (last.x - top_left.x) * (first.y - top_left.y) - (last.y - top_left.y) * (first.x - top_left.x);
+    if cross < 0.0 {
+        std::mem::swap(&mut first, &mut last);
+    }
+    Some([top_left, last, first])
+}
+
+/**
+Samples the symbol and decodes it. Dimension is estimated from the finder distances,
+the neighbouring sizes and the size from the version information are tried as well.
+Parameters:
+corners - top left, top right and bottom left finder patterns.
+**/
+fn decode_symbol(image: &BitMatrix, corners: &[FinderPattern; 3]) -> Option<String> {
+    let [top_left, top_right, bottom_left] = corners;
+    // Row cross section of a rotated finder is wider, the module size is measured along the symbol sides.
+    let module_size = (module_size_towards(image, top_left, top_right) + module_size_towards(image, top_right, top_left)
+        + module_size_towards(image, top_left, bottom_left) + module_size_towards(image, bottom_left, top_left)) / 4.0;
+    if !module_size.is_finite() || module_size < 1.0 {
+        return None;
+    }
+    let dist = |p: &FinderPattern, q: &FinderPattern| ((p.x - q.x).powi(2) + (p.y - q.y).powi(2)).sqrt();
+    let estimate = (dist(top_left, top_right) + dist(top_left, bottom_left)) / 2.0 / module_size + 7.0;
+    let nearest = ((estimate - 1.0) / 4.0).round() as usize * 4 + 1;
+    let mut dims = vec![nearest, nearest + 4, nearest.saturating_sub(4)];
+    let mut i = 0;
+    while i < dims.len() {
+        let dim = dims[i];
+        i += 1;
+        if !(21..=177).contains(&dim) {
+            continue;
+        }
+        let grid = match sample_symbol(image, corners, dim) {
+            Some(grid) => grid,
+            None => continue,
+        };
+        if let Some(text) = decode_qr(&grid) {
+            return Some(text);
+        }
+        if let Some(version) = read_version(&grid) {
+            if !dims.contains(&(version * 4 + 17)) {
+                dims.push(version * 4 + 17);
+            }
+        }
+    }
+    None
+}
+
+/**
+Module size of the finder pattern measured along the line towards the other finder pattern.
+Dark center, light ring and dark ring are 3.5 modules from the center to both directions.
+**/
+fn module_size_towards(image: &BitMatrix, from: &FinderPattern, to: &FinderPattern) -> f32 {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let len = (dx * dx + dy * dy).sqrt();
+    let forward = finder_run_length(image, from, dx / len, dy / len);
+    let backward = finder_run_length(image, from, -dx / len, -dy / len);
+    match (forward, backward) {
+        (Some(f), Some(b)) => (f + b) / 7.0,
+        (Some(f), None) => f / 3.5,
+        (None, Some(b)) => b / 3.5,
+        (None, None) => from.module_size,
+    }
+}
+
+///Distance from the center to the end of the dark-light-dark runs, None if the image border comes first.
+fn finder_run_length(image: &BitMatrix, from: &FinderPattern, dx: f32, dy: f32) -> Option<f32> {
+    let mut state = 0;
+    let mut t = 0.0;
+    // Runs are at most 3.5 modules of the row estimate, the limit allows a lot of perspective.
+    while t < from.module_size * 10.0 {
+        let dark = image.get_checked((from.x + dx * t) as i32, (from.y + dy * t) as i32);
+        let x = from.x + dx * t;
+        let y = from.y + dy * t;
+        if x < 0.0 || y < 0.0 || x >= image.width as f32 || y >= image.height as f32 {
+            return None;
+        }
+        if dark == (state % 2 == 1) {
+            state += 1;
+            if state == 3 {
+                return Some(t);
+            }
+        }
+        t += 0.5;
+    }
+    None
+}
+
+/**
+Maps the finder pattern centers and the bottom right corner to the module grid.
+Bottom right corner is the alignment pattern if it is found, otherwise the corner of the parallelogram.
+**/
+fn sample_symbol(image: &BitMatrix, corners: &[FinderPattern; 3], dim: usize) -> Option<BitMatrix> {
+    let [top_left, top_right, bottom_left] = corners;
+    let corner_x = top_right.x - top_left.x + bottom_left.x;
+    let corner_y = top_right.y - top_left.y + bottom_left.y;
+    let dim_minus_three = dim as f32 - 3.5;
+    let mut source_corner = dim_minus_three;
+    let mut target = (corner_x, corner_y);
+    if dim > 21 {
+        // Alignment pattern center is 3 modules closer to the top left than the corner estimate.
+        let modules_between = dim as f32 - 7.0;
+        let correction = 1.0 - 3.0 / modules_between;
+        let est_x = top_left.x + correction * (corner_x - top_left.x);
+        let est_y = top_left.y + correction * (corner_y - top_left.y);
+        let axes = [
+            ((top_right.x - top_left.x) / modules_between, (top_right.y - top_left.y) / modules_between),
+            ((bottom_left.x - top_left.x) / modules_between, (bottom_left.y - top_left.y) / modules_between),
+        ];
+        for allowance in [4.0, 8.0, 16.0] {
+            if let Some(alignment) = find_alignment_pattern(image, est_x, est_y, &axes, allowance) {
+                source_corner = dim_minus_three - 3.0;
+                target = alignment;
+                break;
+            }
+        }
+    }
+    let src = [(3.5, 3.5), (dim_minus_three, 3.5), (source_corner, source_corner), (3.5, dim_minus_three)];
+    let dst = [(top_left.x, top_left.y), (top_right.x, top_right.y), target, (bottom_left.x, bottom_left.y)];
+    let transform = PerspectiveTransform::quadrilateral_to_quadrilateral(&src, &dst);
+    sample_grid(image, dim, dim, &transform)
+}
+
+/**
+Searches the alignment pattern, a dark module in the middle of a light 3x3 square inside
+a dark 5x5 square, around the estimated center. Positions with the fewest wrong modules
+are preferred, ties are resolved by the distance to the estimate.
+Parameters:
+est_x, est_y - estimated center.
+axes - one module step along the symbol rows and columns in the image.
+allowance - search area is this many modules to each direction.
+**/
+fn find_alignment_pattern(image: &BitMatrix, est_x: f32, est_y: f32, axes: &[(f32,f32);2], allowance: f32) -> Option<(f32,f32)> {
+    let module_size = (axes[0].0.hypot(axes[0].1) + axes[1].0.hypot(axes[1].1)) / 2.0;
+    let step = (module_size / 2.0).max(1.0);
+    let steps = (allowance * module_size / step) as i32;
+    let mut best = (ALIGNMENT_MAX_WRONG + 1, f32::MAX, (est_x, est_y));
+    for i in -steps..=steps {
+        for j in -steps..=steps {
+            let (x, y) = (est_x + j as f32 * step, est_y + i as f32 * step);
+            let wrong = wrong_alignment_modules(image, x, y, axes);
+            let distance = (i * i + j * j) as f32;
+            if wrong < best.0 || (wrong == best.0 && distance < best.1) {
+                best = (wrong, distance, (x, y));
+            }
+        }
+    }
+    if best.0 > ALIGNMENT_MAX_WRONG {
+        return None;
+    }
+    // Center of the pixel positions that match as well as the best one.
+    let (bx, by) = best.2;
+    let range = step.ceil() as i32;
+    let (mut sum_x, mut sum_y, mut count) = (0.0, 0.0, 0.0);
+    for i in -range..=range {
+        for j in -range..=range {
+            let (x, y) = (bx + j as f32, by + i as f32);
+            if wrong_alignment_modules(image, x, y, axes) <= best.0 {
+                sum_x += x;
+                sum_y += y;
+                count += 1.0;
+            }
+        }
+    }
+    Some((sum_x / count, sum_y / count))
+}
+
+///Number of the 5x5 alignment pattern modules that have a wrong color when centered at x, y.
+fn wrong_alignment_modules(image: &BitMatrix, x: f32, y: f32, axes: &[(f32,f32);2]) -> usize {
+    let mut wrong = 0;
+    for i in -2_i32..=2 {
+        for j in -2_i32..=2 {
+            let px = x + j as f32 * axes[0].0 + i as f32 * axes[1].0;
+            let py = y + j as f32 * axes[0].1 + i as f32 * axes[1].1;
+            let dark = i.abs().max(j.abs()) != 1;
+            if image.get_checked(px as i32, py as i32) != dark {
+                wrong += 1;
+            }
+        }
+    }
+    wrong
+}
+
+/// Version 1-M symbol of "Hello, QR 123", "1" is a dark module.
+#[cfg(test)]
+pub(crate) const QR_MODULES: [&str; 21] = [
+    "111111101001101111111", "100000101110001000001", "101110100011101011101", "101110101110001011101",
+    "101110100101001011101", "100000100111101000001", "111111101010101111111", "000000001011100000000",
+    "101101110111101001011", "110001011000100001101", "111011101011011100011", "010001010101100011010",
+    "110111100000110100001", "000000001000101010101", "111111101100011010000", "100000101110000101110",
+    "101110100001000111110", "101110101111110001110", "101110101100100100100", "100000100110001110001",
+    "111111101000001100100",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::QR_MODULES;
+    use crate::decoder_options::DecoderOptions;
+    use crate::symbology::Symbology;
+    use crate::test_images::read_modules;
+
+    fn qr_options() -> DecoderOptions {
+        DecoderOptions::default().symbologies(&[Symbology::QrCode])
+    }
+
+    #[test]
+    fn reads_qr_code() {
+        assert_eq!(read_modules(&QR_MODULES, 0.0, &qr_options()), vec!["QR Code Hello, QR 123"]);
+    }
+
+    #[test]
+    fn corrects_errors() {
+        let mut rows : Vec<String> = QR_MODULES.iter().map(|r| r.to_string()).collect();
+        // Two data modules in the bottom right corner are flipped.
+        rows[20].replace_range(19..21, "11");
+        let rows : Vec<&str> = rows.iter().map(|r| r.as_str()).collect();
+        assert_eq!(read_modules(&rows, 0.0, &qr_options()), vec!["QR Code Hello, QR 123"]);
+    }
+}
@@ -0,0 +1,161 @@
+/**
+Galois field GF(2^m) used by the error correction of the 2D symbologies.
+    size - number of the field elements, 2^m.
+    exp - powers of the primitive element, doubled so that products do not need the modulo.
+    log - discrete logarithms of the elements, log[0] is not used.
+    generator_base - power of the first root of the generator polynomial, 0 for QR Code and 1 for the others.
+**/
+pub struct GaloisField {
+    size: usize,
+    exp: Vec<u16>,
+    log: Vec<u16>,
+    generator_base: usize,
+}
+
+impl GaloisField {
+    /**
+    Builds the field tables.
+    Parameters:
+    primitive - primitive polynomial of the field with the x^m term, e.g. 0x11d for QR Code.
+    size - number of the field elements.
+    generator_base - power of the first root of the generator polynomial.
+    **/
+    pub fn new(primitive: usize, size: usize, generator_base: usize) -> GaloisField {
+        let mut exp = vec![0_u16; size * 2];
+        let mut log = vec![0_u16; size];
+        let mut x = 1;
+        for (i, e) in exp.iter_mut().take(size - 1).enumerate() {
+            *e = x as u16;
+            log[x] = i as u16;
+            x <<= 1;
+            if x >= size {
+                x ^= primitive;
+            }
+        }
+        for i in size - 1..size * 2 {
+            exp[i] = exp[i - (size - 1)];
+        }
+        GaloisField { size, exp, log, generator_base }
+    }
+
+    fn mul(&self, a: u16, b: u16) -> u16 {
+        if a == 0 || b == 0 {
+            return 0;
+        }
+        self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+    }
+
+    fn inv(&self, a: u16) -> u16 {
+        self.exp[self.size - 1 - self.log[a as usize] as usize]
+    }
+
+    fn pow_of_alpha(&self, power: usize) -> u16 {
+        self.exp[power % (self.size - 1)]
+    }
+
+    ///Value of the polynomial with the lowest degree coefficient first.
+    fn eval(&self, poly: &[u16], x: u16) -> u16 {
+        poly.iter().rev().fold(0, |acc, c| self.mul(acc, x) ^ c)
+    }
+}
+
+/**
+Corrects errors in place. First codeword has the highest power in the code polynomial.
+Returns number of corrected codewords or None if there are too many errors.
+Parameters:
+field - Galois field of the symbology.
+codewords - data codewords followed by the error correction codewords.
+ec_count - number of the error correction codewords.
+**/
+pub fn correct_errors(field: &GaloisField, codewords: &mut [u16], ec_count: usize) -> Option<usize> {
+    let n = codewords.len();
+    if ec_count == 0 || ec_count >= n || n >= field.size {
+        return if ec_count < n {Some(0)} else {None};
+    }
+    let code: Vec<u16> = codewords.iter().rev().cloned().collect();
+    let syndromes: Vec<u16> = (0..ec_count)
+        .map(|i| field.eval(&code, field.pow_of_alpha(i + field.generator_base)))
+        .collect();
+    if syndromes.iter().all(|s| *s == 0) {
+        return Some(0);
+    }
+    let locator = berlekamp_massey(field, &syndromes);
+    let errors = locator.len() - 1;
+    if errors * 2 > ec_count {
+        return None;
+    }
+    // Chien search, error at power p makes alpha^-p a root of the locator.
+    let mut positions = Vec::with_capacity(errors);
+    for p in 0..n {
+        if field.eval(&locator, field.pow_of_alpha(field.size - 1 - p)) == 0 {
+            positions.push(p);
+        }
+    }
+    if positions.len() != errors {
+        return None;
+    }
+    // Forney algorithm, evaluator is S(x)L(x) mod x^ec_count.
+    let mut evaluator = vec![0_u16; ec_count];
+    for (i, s) in syndromes.iter().enumerate() {
+        for (j, l) in locator.iter().enumerate() {
+            if i + j < ec_count {
+                evaluator[i + j] ^= field.mul(*s, *l);
+            }
+        }
+    }
+    let derivative: Vec<u16> = locator.iter().enumerate().skip(1)
+        .map(|(i, l)| if i % 2 == 1 {*l} else {0})
+        .collect();
+    for p in positions {
+        let x_inv = field.pow_of_alpha(field.size - 1 - p);
+        let denominator = field.eval(&derivative, x_inv);
+        if denominator == 0 {
+            return None;
+        }
+        let magnitude = field.mul(field.eval(&evaluator, x_inv), field.inv(denominator));
+        // Error value has the factor X^(1-b) where X = alpha^p is the error locator.
+        let power = p * (field.size - field.generator_base);
+        let magnitude = field.mul(magnitude, field.pow_of_alpha(power));
+        codewords[n - 1 - p] ^= magnitude;
+    }
+    Some(errors)
+}
+
+///Error locator polynomial with the lowest degree coefficient first.
+fn berlekamp_massey(field: &GaloisField, syndromes: &[u16]) -> Vec<u16> {
+    let mut locator = vec![1_u16];
+    let mut prev = vec![1_u16];
+    let mut errors = 0;
+    let mut shift = 1;
+    let mut prev_discrepancy = 1;
+    for n in 0..syndromes.len() {
+        let mut discrepancy = syndromes[n];
+        for i in 1..=errors {
+            discrepancy ^= field.mul(*locator.get(i).unwrap_or(&0), syndromes[n - i]);
+        }
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+        let coef = field.mul(discrepancy, field.inv(prev_discrepancy));
+        let mut next = locator.clone();
+        if next.len() < prev.len() + shift {
+            next.resize(prev.len() + shift, 0);
+        }
+        for (i, p) in prev.iter().enumerate() {
+            next[i + shift] ^= field.mul(coef, *p);
+        }
+        if 2 * errors <= n {
+            prev = locator;
+            errors = n + 1 - errors;
+            prev_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+        locator = next;
+    }
+    locator.truncate(errors + 1);
+    locator.resize(errors + 1, 0);
+    locator
+}
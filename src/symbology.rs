@@ -0,0 +1,196 @@
+use std::fmt;
+use crate::dark_area::Point;
+
+/// Confidence of the linear barcode that was read from one scan line.
+const LINE_CONFIDENCE: f32 = 0.5;
+
+///Barcode symbologies that the reader is able to recognise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symbology {
+    Ean13,
+    Ean8,
+    UpcE,
+    Code128,
+    Gs1128,
+    Code39,
+    Code39Extended,
+    Code93,
+    Code93Extended,
+    Itf,
+    Itf14,
+    Codabar,
+    DataBar,
+    DataBarExpanded,
+    QrCode,
+    DataMatrix,
+    Aztec,
+}
+
+impl Symbology {
+    ///All of the symbologies, DecoderOptions::symbologies(&Symbology::ALL) searches for the 2D symbols too.
+    pub const ALL: [Symbology; 17] = [
+        Symbology::Ean13, Symbology::Ean8, Symbology::UpcE, Symbology::Code128, Symbology::Gs1128, Symbology::Code39,
+        Symbology::Code39Extended, Symbology::Code93, Symbology::Code93Extended, Symbology::Itf, Symbology::Itf14,
+        Symbology::Codabar, Symbology::DataBar, Symbology::DataBarExpanded, Symbology::QrCode, Symbology::DataMatrix, Symbology::Aztec,
+    ];
+
+    ///True for the symbologies that are read from the scan lines, false for the 2D symbols.
+    pub fn is_linear(self) -> bool {
+        !matches!(self, Symbology::QrCode | Symbology::DataMatrix | Symbology::Aztec)
+    }
+}
+
+impl fmt::Display for Symbology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Symbology::Ean13 => "EAN-13",
+            Symbology::Ean8 => "EAN-8",
+            Symbology::UpcE => "UPC-E",
+            Symbology::Code128 => "Code 128",
+            Symbology::Gs1128 => "GS1-128",
+            Symbology::Code39 => "Code 39",
+            Symbology::Code39Extended => "Code 39 Extended",
+            Symbology::Code93 => "Code 93",
+            Symbology::Code93Extended => "Code 93 Extended",
+            Symbology::Itf => "ITF",
+            Symbology::Itf14 => "ITF-14",
+            Symbology::Codabar => "Codabar",
+            Symbology::DataBar => "GS1 DataBar",
+            Symbology::DataBarExpanded => "GS1 DataBar Expanded",
+            Symbology::QrCode => "QR Code",
+            Symbology::DataMatrix => "Data Matrix",
+            Symbology::Aztec => "Aztec",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+///Scan lines that the linear barcodes are read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScanPass {
+    Rows,
+    Columns,
+    Rotated,
+}
+
+impl ScanPass {
+    ///Pass of the scan lines at the angle, 0 degrees are the rows and 90 degrees the columns, in either direction.
+    pub fn from_deg(deg: u16) -> ScanPass {
+        match deg % 180 {
+            0 => ScanPass::Rows,
+            90 => ScanPass::Columns,
+            _ => ScanPass::Rotated,
+        }
+    }
+}
+
+impl fmt::Display for ScanPass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ScanPass::Rows => "rows",
+            ScanPass::Columns => "columns",
+            ScanPass::Rotated => "rotated",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/**
+Decoded barcode content together with the symbology it was read from and the place it was found at.
+    text - digits or characters as they are encoded in the barcode.
+    expanded - zero-suppressed UPC-E code expanded to EAN-13 (UPC-A with a leading zero).
+    addon - digits of the EAN-2 or EAN-5 supplement, only set if the supplement is valid.
+    start, end - image coordinates of the first and the last bar on the scan line, None for the 2D symbols and GS1 DataBar.
+    row - position of the scan line, the row number for the rows and the column number for the columns. None for the 2D symbols.
+    deg - angle of the scan line the linear barcode was found from, in the direction from the start of the code to its end.
+        Lines read in the opposite direction have 180 added, 180 is a row read from the right to the left. None for the 2D symbols.
+    pass - scan pass the linear barcode was found from, None for the 2D symbols.
+    module_widths - widths of the bars and spaces of the EAN and UPC digits in modules, 4 for each digit. Empty for the other symbologies.
+    reversed - linear barcode goes in the opposite direction of its scan line, deg is 180 or more. Upside down on the rows.
+    confidence - 0.5 for the linear barcode read from one line, every other line with the same content halves the rest.
+        2D symbols are verified by the error correction and have 1.0.
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Barcode {
+    pub symbology: Symbology,
+    pub text: String,
+    pub expanded: Option<String>,
+    pub addon: Option<String>,
+    pub start: Option<Point>,
+    pub end: Option<Point>,
+    pub row: Option<u32>,
+    pub deg: Option<u16>,
+    pub pass: Option<ScanPass>,
+    pub module_widths: Vec<u8>,
+    pub reversed: bool,
+    pub confidence: f32,
+}
+
+impl Barcode {
+    pub fn new(symbology: Symbology, text: String) -> Barcode {
+        Barcode {
+            symbology,
+            text,
+            expanded: None,
+            addon: None,
+            start: None,
+            end: None,
+            row: None,
+            deg: None,
+            pass: None,
+            module_widths: Vec::new(),
+            reversed: false,
+            confidence: 1.0,
+        }
+    }
+
+    /**
+    Sets the position and the angle of the scan line the code was found from and the pass of that line.
+    Angle is in the direction of the code, the angles from 180 on are the lines read from the end and the code is reversed.
+    **/
+    pub fn set_scan_line(&mut self, row: u32, deg: u16) {
+        self.row = Some(row);
+        self.deg = Some(deg);
+        self.pass = Some(ScanPass::from_deg(deg));
+        self.reversed = deg >= 180;
+    }
+
+    ///Sets the image coordinates of the first and the last bar.
+    pub fn set_span(&mut self, start: Point, end: Point) {
+        self.start = Some(start);
+        self.end = Some(end);
+    }
+
+    ///Sets the confidence of the code that was read from the number of scan lines.
+    pub fn set_line_count(&mut self, lines: u32) {
+        self.confidence = 1.0 - (1.0 - LINE_CONFIDENCE).powi(lines as i32);
+    }
+
+    ///Adds the confidence of the same code read again, the doubts of both reads are multiplied.
+    pub fn add_confidence(&mut self, confidence: f32) {
+        self.confidence = 1.0 - (1.0 - self.confidence) * (1.0 - confidence);
+    }
+
+    ///True if the codes have the same content, regardless of where they were found from.
+    pub fn has_same_content(&self, other: &Barcode) -> bool {
+        self.symbology == other.symbology && self.text == other.text && self.expanded == other.expanded && self.addon == other.addon
+    }
+}
+impl fmt::Display for Barcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.symbology, self.text)?;
+        if let Some(addon) = &self.addon {
+            write!(f, " {}", addon)?;
+        }
+        if let Some(expanded) = &self.expanded {
+            write!(f, " ({})", expanded)?;
+        }
+        if let (Some(pass), Some(deg)) = (self.pass, self.deg) {
+            write!(f, " {} deg {}", pass, deg)?;
+        }
+        if self.reversed {
+            write!(f, " reversed")?;
+        }
+        Ok(())
+    }
+}
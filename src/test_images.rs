@@ -0,0 +1,133 @@
+//! Images of the linear barcodes for the tests, drawn from the bar and space widths.
+
+use crate::barcode_detector::process_image_with_options;
+use crate::decoder_options::DecoderOptions;
+use crate::pixel_sources::RawImage;
+use crate::symbology::Barcode;
+
+/// Pixels of one module.
+const MODULE: usize = 3;
+
+/// Light modules before and after the code.
+const QUIET_ZONE: usize = 12;
+
+/// Height of the bars in modules.
+const BAR_HEIGHT: usize = 30;
+
+/**
+Draws the code rotated by deg degrees around its center, the widths start with a bar.
+Returns the gray pixels and the width and height of the image.
+**/
+pub(crate) fn draw_widths(widths: &[usize], deg: f32) -> (Vec<u8>, u32, u32) {
+    let mut modules : Vec<bool> = Vec::new();
+    for (i, w) in widths.iter().enumerate() {
+        modules.extend(std::iter::repeat_n(i % 2 == 0, *w));
+    }
+    let size = (modules.len() + 2 * QUIET_ZONE, BAR_HEIGHT);
+    draw_rotated(size, deg, |x, _| x.checked_sub(QUIET_ZONE).and_then(|x| modules.get(x)) == Some(&true))
+}
+
+/// Thickness of the bearer bars in modules.
+const BEARER_WIDTH: usize = 4;
+
+/**
+Draws the code inside a bearer box rotated by deg degrees, like the ITF-14 on the shipping cartons.
+The box is drawn around the code and its quiet zones, the bars touch the top and the bottom bearer.
+**/
+pub(crate) fn draw_bearer_box(widths: &[usize], deg: f32) -> (Vec<u8>, u32, u32) {
+    let mut modules : Vec<bool> = Vec::new();
+    for (i, w) in widths.iter().enumerate() {
+        modules.extend(std::iter::repeat_n(i % 2 == 0, *w));
+    }
+    let margin = QUIET_ZONE / 3;
+    let outer = (margin + BEARER_WIDTH, margin + BEARER_WIDTH);
+    let inner = (modules.len() + 2 * QUIET_ZONE, BAR_HEIGHT);
+    let size = (inner.0 + 2 * outer.0, inner.1 + 2 * outer.1);
+    draw_rotated(size, deg, |x, y| {
+        let in_box = x >= margin && y >= margin && x < size.0 - margin && y < size.1 - margin;
+        let (x, y) = (x.wrapping_sub(outer.0), y.wrapping_sub(outer.1));
+        if x < inner.0 && y < inner.1 {
+            x.checked_sub(QUIET_ZONE).and_then(|x| modules.get(x)) == Some(&true)
+        } else {
+            in_box
+        }
+    })
+}
+
+/**
+Draws the 2D symbol rotated by deg degrees around its center, "1" is a dark module.
+Returns the gray pixels and the width and height of the image.
+**/
+fn draw_modules(rows: &[&str], deg: f32) -> (Vec<u8>, u32, u32) {
+    let quiet_zone = QUIET_ZONE / 3;
+    let size = (rows[0].len() + 2 * quiet_zone, rows.len() + 2 * quiet_zone);
+    draw_rotated(size, deg, |x, y| {
+        let (x, y) = (x.wrapping_sub(quiet_zone), y.wrapping_sub(quiet_zone));
+        rows.get(y).and_then(|row| row.as_bytes().get(x)) == Some(&b'1')
+    })
+}
+
+///Draws the modules of the given size rotated around the center, dark(x, y) tells if the module is dark.
+fn draw_rotated<F: Fn(usize, usize) -> bool>(size: (usize, usize), deg: f32, dark: F) -> (Vec<u8>, u32, u32) {
+    let (len, height) = ((size.0 * MODULE) as f32, (size.1 * MODULE) as f32);
+    let (sin, cos) = deg.to_radians().sin_cos();
+    let width = (len * cos.abs() + height * sin.abs()).ceil() as u32 + 2;
+    let image_height = (len * sin.abs() + height * cos.abs()).ceil() as u32 + 2;
+    let (cx, cy) = (width as f32 / 2.0, image_height as f32 / 2.0);
+    let mut pixels = vec![255; (width * image_height) as usize];
+    for y in 0..image_height {
+        for x in 0..width {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            let u = dx * cos + dy * sin + len / 2.0;
+            let v = -dx * sin + dy * cos + height / 2.0;
+            if u < 0.0 || v < 0.0 || u >= len || v >= height {
+                continue;
+            }
+            if dark(u as usize / MODULE, v as usize / MODULE) {
+                pixels[(y * width + x) as usize] = 0;
+            }
+        }
+    }
+    (pixels, width, image_height)
+}
+
+///Widths of the bars and spaces from the modules, "1" is a bar. Spaces before the first bar are left out.
+pub(crate) fn module_widths(modules: &str) -> Vec<usize> {
+    let mut widths : Vec<usize> = Vec::new();
+    let mut last = b'0';
+    for m in modules.bytes().skip_while(|m| *m == b'0') {
+        if m == last {
+            *widths.last_mut().unwrap() += 1;
+        } else {
+            widths.push(1);
+        }
+        last = m;
+    }
+    widths
+}
+
+///Draws the code in the bearer box at the angle and reads the texts with the options.
+pub(crate) fn read_bearer_box(widths: &[usize], deg: f32, options: &DecoderOptions) -> Vec<String> {
+    let (pixels, width, height) = draw_bearer_box(widths, deg);
+    let img = RawImage::new(&pixels, width, height, 1, width as usize).unwrap();
+    process_image_with_options(&img, (width, height), options).unwrap().iter().map(|c| format!("{} {}", c.symbology, c.text)).collect()
+}
+
+///Draws the code at the angle and reads it with the options.
+pub(crate) fn read_widths(widths: &[usize], deg: f32, options: &DecoderOptions) -> Vec<Barcode> {
+    let (pixels, width, height) = draw_widths(widths, deg);
+    let img = RawImage::new(&pixels, width, height, 1, width as usize).unwrap();
+    process_image_with_options(&img, (width, height), options).unwrap()
+}
+
+///Draws the 2D symbol at the angle and reads it with the options.
+pub(crate) fn read_modules(rows: &[&str], deg: f32, options: &DecoderOptions) -> Vec<String> {
+    let (pixels, width, height) = draw_modules(rows, deg);
+    let img = RawImage::new(&pixels, width, height, 1, width as usize).unwrap();
+    process_image_with_options(&img, (width, height), options).unwrap().iter().map(|c| format!("{} {}", c.symbology, c.text)).collect()
+}
+
+///Texts of the codes with the symbology name, like "Code 39 ABC".
+pub(crate) fn read_texts(widths: &[usize], deg: f32, options: &DecoderOptions) -> Vec<String> {
+    read_widths(widths, deg, options).iter().map(|c| format!("{} {}", c.symbology, c.text)).collect()
+}